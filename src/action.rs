@@ -1,6 +1,15 @@
+use std::{path::PathBuf, time::Duration};
+
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
+use crate::{
+    app::Mode,
+    audio::PlaybackEvent,
+    history::HistoryEntry,
+    library::{Track, scanner::ScanReport},
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
 pub enum Action {
     Tick,
@@ -10,9 +19,45 @@ pub enum Action {
     Resume,
     Quit,
     ClearScreen,
+    /// Write the session snapshot (queue, current track and position) to the data directory.
+    SaveSession,
     Error(String),
     Key(crossterm::event::KeyEvent),
     Noop,
     Help,
-    PressTab, 
+    PressTab,
+    SetMode(Mode),
+    TogglePause,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    ToggleShuffle,
+    /// Switch repeat between off, all and one.
+    CycleRepeat,
+    ToggleCrossfade,
+    /// Seconds to move forward, or backward if negative.
+    SeekBy(i64),
+    SeekToPercent(u8),
+    SeekTo(Duration),
+    OpenSeekPrompt,
+    /// Add the track under the song list cursor to the end of the queue.
+    Enqueue,
+    /// Insert the track under the song list cursor right after the current one.
+    PlayNext,
+    /// Like or unlike the focused track: the song list or queue selection, or the one playing.
+    ToggleFavorite,
+    /// Ask for a playlist name and add the focused track to it.
+    AddToPlaylist,
+    Playback(PlaybackEvent),
+    /// A track started, was skipped or played to the end; goes to the play history.
+    RecordPlay(HistoryEntry),
+    ToggleScanReport,
+    /// Show or hide the listening statistics.
+    ToggleStats,
+    LibraryTrackAdded(Box<Track>),
+    /// A file, or a whole folder, disappeared from the library.
+    LibraryTrackRemoved(PathBuf),
+    /// Files probed so far and the total number of files found.
+    LibraryScanProgress(usize, usize),
+    LibraryScanDone(ScanReport),
 }
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use color_eyre::Result;
 use crossterm::event::KeyEvent;
 use ratatui::prelude::Rect;
@@ -7,11 +9,15 @@ use tracing::{debug, info};
 
 use crate::{
     action::Action,
-    components::{Component, fps::FpsCounter, home::Home,},
-    config::Config,
+    cli::Cli,
+    components::{Component, fps::FpsCounter, home::Home},
+    config::{Config, expand_path},
     tui::{Event, Tui},
 };
 
+/// How often the session snapshot is saved while the app runs, on top of quit and suspend.
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
 pub struct App {
     config: Config,
     tick_rate: f64,
@@ -21,6 +27,7 @@ pub struct App {
     should_suspend: bool,
     mode: Mode,
     last_tick_key_events: Vec<KeyEvent>,
+    last_session_save: Instant,
     action_tx: mpsc::UnboundedSender<Action>,
     action_rx: mpsc::UnboundedReceiver<Action>,
 }
@@ -29,20 +36,38 @@ pub struct App {
 pub enum Mode {
     #[default]
     Home,
+    /// A text prompt is open; keys go to it instead of the Home keybindings.
+    Input,
 }
 
 impl App {
-    pub fn new(tick_rate: f64, frame_rate: f64) -> Result<Self> {
+    pub fn new(args: &Cli) -> Result<Self> {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
+        let mut config = Config::new()?;
+        // Directories given on the command line replace the configured library roots.
+        if !args.music_dir.is_empty() {
+            config.library.roots = args
+                .music_dir
+                .iter()
+                .map(|dir| expand_path(&dir.to_string_lossy()))
+                .collect();
+        }
+        if let Some(output) = &args.output {
+            config.player.output = output.clone();
+        }
         Ok(Self {
-            tick_rate,
-            frame_rate,
-            components: vec![Box::new(Home::new()), Box::new(FpsCounter::default())],
+            tick_rate: args.tick_rate,
+            frame_rate: args.frame_rate,
+            components: vec![
+                Box::new(Home::new().rescan(args.rescan).resume(!args.no_resume)),
+                Box::new(FpsCounter::default()),
+            ],
             should_quit: false,
             should_suspend: false,
-            config: Config::new()?,
+            config,
             mode: Mode::Home,
             last_tick_key_events: Vec::new(),
+            last_session_save: Instant::now(),
             action_tx,
             action_rx,
         })
@@ -138,10 +163,21 @@ impl App {
             match action {
                 Action::Tick => {
                     self.last_tick_key_events.drain(..);
+                    if self.last_session_save.elapsed() >= SESSION_SAVE_INTERVAL {
+                        self.save_session()?;
+                    }
+                }
+                // SaveSession встигне обробитись: черга дій дочитується до кінця перед виходом
+                Action::Quit => {
+                    self.should_quit = true;
+                    self.save_session()?;
+                }
+                Action::Suspend => {
+                    self.should_suspend = true;
+                    self.save_session()?;
                 }
-                Action::Quit => self.should_quit = true,
-                Action::Suspend => self.should_suspend = true,
                 Action::Resume => self.should_suspend = false,
+                Action::SetMode(mode) => self.mode = mode,
                 Action::ClearScreen => tui.terminal.clear()?,
                 Action::Resize(w, h) => self.handle_resize(tui, w, h)?,
                 Action::Render => self.render(tui)?,
@@ -156,6 +192,12 @@ impl App {
         Ok(())
     }
 
+    fn save_session(&mut self) -> Result<()> {
+        self.last_session_save = Instant::now();
+        self.action_tx.send(Action::SaveSession)?;
+        Ok(())
+    }
+
     fn handle_resize(&mut self, tui: &mut Tui, w: u16, h: u16) -> Result<()> {
         tui.resize(Rect::new(0, 0, w, h))?;
         self.render(tui)?;
@@ -0,0 +1,9 @@
+//! Audio playback: decoding tracks into samples and playing them on a dedicated engine thread.
+
+pub mod codecs;
+pub mod engine;
+pub mod output;
+pub mod source;
+
+pub use engine::{AudioEngine, Command, PlaybackEvent};
+pub use source::{Follower, GaplessSource, SourceControl, TrackSource};
@@ -0,0 +1,32 @@
+//! The decoders every part of the app uses: the ones symphonia is built with plus Opus, which
+//! symphonia 0.5 can demux but not decode.
+
+mod opus;
+
+use lazy_static::lazy_static;
+use symphonia::core::codecs::{CODEC_TYPE_OPUS, CodecParameters, CodecRegistry};
+
+lazy_static! {
+    static ref REGISTRY: CodecRegistry = {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        registry.register_all::<opus::OpusDecoder>();
+        registry
+    };
+}
+
+/// Use this instead of `symphonia::default::get_codecs`, so the scanner accepts exactly the files
+/// the player can play.
+pub fn registry() -> &'static CodecRegistry {
+    &REGISTRY
+}
+
+/// Frames of audio in a track opened with gapless playback, if the container tells. The Ogg
+/// reader of symphonia 0.5 counts the Opus pre-skip in, which the decoder then drops.
+pub fn n_frames(params: &CodecParameters) -> Option<u64> {
+    let frames = params.n_frames?;
+    if params.codec == CODEC_TYPE_OPUS {
+        return Some(frames.saturating_sub(params.delay.unwrap_or_default().into()));
+    }
+    Some(frames)
+}
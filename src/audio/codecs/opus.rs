@@ -0,0 +1,268 @@
+//! A symphonia [`Decoder`] around the pure Rust `opus-decoder` crate.
+
+use opus_decoder::OpusMultistreamDecoder;
+use symphonia::core::{
+    audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec},
+    codecs::{
+        CODEC_TYPE_OPUS, CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult,
+    },
+    errors::{Result, decode_error, unsupported_error},
+    formats::Packet,
+    support_codec,
+};
+
+/// Opus always decodes at 48 kHz; players resample if they need to.
+const SAMPLE_RATE: u32 = 48_000;
+/// The longest packet Opus allows, 120 ms.
+const MAX_FRAMES: usize = SAMPLE_RATE as usize * 120 / 1000;
+
+/// Channels in the order Opus (like Vorbis) interleaves them, for mapping families 0 and 1.
+fn vorbis_channels(count: usize) -> Option<Vec<Channels>> {
+    use Channels as C;
+    let (left, right, centre) = (C::FRONT_LEFT, C::FRONT_RIGHT, C::FRONT_CENTRE);
+    let (rear_left, rear_right, lfe) = (C::REAR_LEFT, C::REAR_RIGHT, C::LFE1);
+    let (side_left, side_right, rear_centre) = (C::SIDE_LEFT, C::SIDE_RIGHT, C::REAR_CENTRE);
+    let channels = match count {
+        1 => vec![left],
+        2 => vec![left, right],
+        3 => vec![left, centre, right],
+        4 => vec![left, right, rear_left, rear_right],
+        5 => vec![left, centre, right, rear_left, rear_right],
+        6 => vec![left, centre, right, rear_left, rear_right, lfe],
+        7 => vec![left, centre, right, side_left, side_right, rear_centre, lfe],
+        8 => vec![
+            left, centre, right, side_left, side_right, rear_left, rear_right, lfe,
+        ],
+        _ => return None,
+    };
+    Some(channels)
+}
+
+/// The "OpusHead" identification header (RFC 7845, section 5.1).
+struct Header {
+    channels: usize,
+    /// Frames at the start of the stream that only prime the decoder.
+    pre_skip: u64,
+    /// Gain to apply to the output, in 1/256 dB.
+    output_gain: i16,
+    streams: usize,
+    coupled_streams: usize,
+    /// Which decoded stream channel feeds each output channel.
+    mapping: Vec<u8>,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 19 || &data[..8] != b"OpusHead" {
+            return decode_error("opus: invalid identification header");
+        }
+        let channels = usize::from(data[9]);
+        let pre_skip = u64::from(u16::from_le_bytes([data[10], data[11]]));
+        let output_gain = i16::from_le_bytes([data[16], data[17]]);
+        match data[18] {
+            0 if (1..=2).contains(&channels) => Ok(Self {
+                channels,
+                pre_skip,
+                output_gain,
+                streams: 1,
+                coupled_streams: channels - 1,
+                mapping: (0..channels as u8).collect(),
+            }),
+            0 => decode_error("opus: invalid channel count"),
+            1 => {
+                let Some(table) = data.get(21..21 + channels) else {
+                    return decode_error("opus: truncated channel mapping");
+                };
+                Ok(Self {
+                    channels,
+                    pre_skip,
+                    output_gain,
+                    streams: usize::from(data[19]),
+                    coupled_streams: usize::from(data[20]),
+                    mapping: table.to_vec(),
+                })
+            }
+            _ => unsupported_error("opus: unsupported channel mapping family"),
+        }
+    }
+}
+
+pub struct OpusDecoder {
+    params: CodecParameters,
+    decoder: OpusMultistreamDecoder,
+    pre_skip: u64,
+    /// Linear factor for the output gain from the header.
+    gain: f32,
+    /// Plane of `buf` for each interleaved channel the decoder outputs.
+    planes: Vec<usize>,
+    /// Interleaved output of the last packet.
+    pcm: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+impl OpusDecoder {
+    fn decode_inner(&mut self, packet: &Packet) -> Result<()> {
+        let Ok(frames) = self
+            .decoder
+            .decode_float(&packet.data, &mut self.pcm, false)
+        else {
+            return decode_error("opus: invalid packet");
+        };
+        self.buf.clear();
+        self.buf.render_reserved(Some(frames));
+        let channels = self.planes.len();
+        for (channel, &plane) in self.planes.iter().enumerate() {
+            let samples = self.pcm.chunks_exact(channels).map(|frame| frame[channel]);
+            for (out, sample) in self.buf.chan_mut(plane).iter_mut().zip(samples) {
+                *out = sample * self.gain;
+            }
+        }
+        // The Ogg reader of symphonia 0.5 counts the pre-skip as audio, so it is dropped here.
+        // Timestamps still include it, which also covers seeking back to the start.
+        let pre_skip = self.pre_skip.saturating_sub(packet.ts()) as usize;
+        let trim_start = pre_skip.max(packet.trim_start() as usize);
+        self.buf.trim(trim_start, packet.trim_end() as usize);
+        Ok(())
+    }
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_OPUS {
+            return unsupported_error("opus: invalid codec type");
+        }
+        let Some(extra_data) = &params.extra_data else {
+            return unsupported_error("opus: missing identification header");
+        };
+        let header = Header::parse(extra_data)?;
+        let Some(order) = vorbis_channels(header.channels) else {
+            return unsupported_error("opus: unsupported channel count");
+        };
+        let Ok(decoder) = OpusMultistreamDecoder::new(
+            SAMPLE_RATE,
+            header.channels,
+            header.streams,
+            header.coupled_streams,
+            &header.mapping,
+        ) else {
+            return decode_error("opus: invalid stream layout");
+        };
+
+        // Planes of an AudioBuffer go in the order of the channel bits, not the Opus order
+        let channels = order.iter().fold(Channels::empty(), |all, &one| all | one);
+        let planes = order
+            .iter()
+            .map(|one| (channels.bits() & (one.bits() - 1)).count_ones() as usize)
+            .collect();
+        let spec = SignalSpec::new(SAMPLE_RATE, channels);
+
+        Ok(Self {
+            params: params.clone(),
+            decoder,
+            pre_skip: header.pre_skip,
+            gain: 10f32.powf(f32::from(header.output_gain) / 256.0 / 20.0),
+            planes,
+            pcm: vec![0.0; MAX_FRAMES * header.channels],
+            buf: AudioBuffer::new(MAX_FRAMES as u64, spec),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        self.decoder.reset();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        if let Err(err) = self.decode_inner(packet) {
+            self.buf.clear();
+            return Err(err);
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, path::Path, sync::Arc, time::Duration};
+
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+    use pretty_assertions::assert_eq;
+    use rodio::Source;
+
+    use crate::{
+        audio::{SourceControl, TrackSource},
+        library::scanner,
+    };
+
+    /// Samples decoded before the real start of the audio, dropped by the player.
+    const PRE_SKIP: u16 = 312;
+
+    /// Writes an Ogg Opus stream of one second of stereo silence.
+    fn write_opus(path: &Path) {
+        let mut writer = PacketWriter::new(File::create(path).unwrap());
+        let mut head = b"OpusHead".to_vec();
+        head.extend([1, 2]);
+        head.extend(PRE_SKIP.to_le_bytes());
+        head.extend(44_100u32.to_le_bytes());
+        head.extend([0, 0, 0]);
+        writer
+            .write_packet(head.into(), 1, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend(4u32.to_le_bytes());
+        tags.extend(b"test");
+        tags.extend(0u32.to_le_bytes());
+        writer
+            .write_packet(tags.into(), 1, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+
+        // CELT fullband 20 ms, stereo, one frame with nothing in it: silence. The last page's
+        // granule position cuts the final packet short, so exactly one second remains.
+        let packet = [31 << 3 | 1 << 2];
+        let end = 48_000 + u64::from(PRE_SKIP);
+        let packets = end.div_ceil(960);
+        for i in 1..=packets {
+            let info = if i == packets {
+                PacketWriteEndInfo::EndStream
+            } else {
+                PacketWriteEndInfo::EndPage
+            };
+            writer
+                .write_packet(Box::new(packet), 1, info, (i * 960).min(end))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_opus_is_scanned_and_played() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("silence.opus");
+        write_opus(&path);
+
+        let track = scanner::probe(&path).unwrap();
+        assert_eq!(track.format, "OPUS");
+        assert_eq!(track.sample_rate, Some(48_000));
+
+        let source = TrackSource::open(&path, Arc::new(SourceControl::default())).unwrap();
+        assert_eq!((source.channels(), source.sample_rate()), (2, 48_000));
+        let samples: Vec<f32> = source.collect();
+        assert!(samples.iter().all(|sample| sample.abs() < 1e-4));
+        assert_eq!(samples.len(), 2 * 48_000);
+        assert_eq!(track.duration, Duration::from_secs(1));
+    }
+}
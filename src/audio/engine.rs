@@ -0,0 +1,635 @@
+//! The audio engine: a thread that owns the output device and plays tracks on request.
+//!
+//! The UI talks to it through [`AudioEngine::send`] and hears back through
+//! [`Action::Playback`] events, so opening, decoding and device calls never run on the UI task.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use color_eyre::Result;
+use rodio::{Sink, Source};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
+
+use super::{
+    Follower, GaplessSource, SourceControl, TrackSource,
+    output::{DeviceOutput, NullOutput, Output, WavOutput},
+};
+use crate::{action::Action, config::OutputBackend};
+
+/// How often the engine reports the playback position while a track is playing.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Requests the UI sends to the engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Stops whatever is playing and starts the file at this path.
+    Load(PathBuf),
+    /// Like [`Command::Load`], but leaves the track paused at `position`.
+    Cue {
+        path: PathBuf,
+        position: Duration,
+    },
+    /// Decodes the file at `path` and queues it behind the playing track. It starts without a
+    /// gap, or fades in over `crossfade` while the playing track fades out. Replaces an earlier
+    /// preload; a `None` path just drops it.
+    Preload {
+        path: Option<PathBuf>,
+        crossfade: Duration,
+    },
+    Play,
+    Pause,
+    Seek(Duration),
+    /// Output amplitude, already mapped through the volume curve.
+    SetVolume(f32),
+    Stop,
+}
+
+/// What happened in the engine, delivered to components as [`Action::Playback`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackEvent {
+    Started(PathBuf),
+    Paused,
+    Resumed,
+    Stopped,
+    Position(Duration),
+    /// The track at this path played to its end.
+    Finished(PathBuf),
+    /// The playing track ended and the preloaded one at this path took over seamlessly.
+    Continued(PathBuf),
+    Error(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// Handle to the engine thread. Dropping it shuts the thread down.
+pub struct AudioEngine {
+    commands: Option<mpsc::Sender<Command>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AudioEngine {
+    /// Starts the engine playing through `backend`. If the backend cannot be opened the engine
+    /// reports a [`PlaybackEvent::Error`] and ignores all commands.
+    pub fn spawn(backend: OutputBackend, events: UnboundedSender<Action>) -> Self {
+        let (commands, rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("audio-engine".into())
+            .spawn(move || {
+                // The device stream is not `Send`, so it has to be opened on the engine thread.
+                let output: Result<Box<dyn Output>> = match &backend {
+                    OutputBackend::Device => DeviceOutput::open().map(|o| Box::new(o) as _),
+                    OutputBackend::Null => Ok(Box::new(NullOutput::new())),
+                    OutputBackend::Wav(path) => WavOutput::create(path).map(|o| Box::new(o) as _),
+                };
+                match output {
+                    Ok(output) => run(Engine::new(output), rx, events),
+                    Err(err) => {
+                        let message = format!("Не вдалося відкрити аудіовихід: {err}");
+                        let _ = events.send(Action::Playback(PlaybackEvent::Error(message)));
+                    }
+                }
+            })
+            .map_err(|err| warn!("Failed to start the audio engine: {err}"))
+            .ok();
+        Self {
+            commands: Some(commands),
+            thread,
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        if let Some(commands) = &self.commands {
+            // The thread is gone only if the device failed to open, which was reported already.
+            let _ = commands.send(command);
+        }
+    }
+}
+
+impl Drop for AudioEngine {
+    fn drop(&mut self) {
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(mut engine: Engine, commands: mpsc::Receiver<Command>, events: UnboundedSender<Action>) {
+    loop {
+        match commands.recv_timeout(POLL_INTERVAL) {
+            Ok(command) => engine.handle(command),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        engine.poll();
+        for event in engine.take_events() {
+            if events.send(Action::Playback(event)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// A track decoded ahead of time to follow the playing one.
+struct Next {
+    path: PathBuf,
+    control: Arc<SourceControl>,
+    /// Held back until the crossfade starts; `None` once it waits to follow the current track.
+    source: Option<TrackSource>,
+    duration: Option<Duration>,
+    crossfade: Duration,
+}
+
+/// Playback state, kept apart from the thread so it can be driven directly in tests.
+struct Engine {
+    output: Box<dyn Output>,
+    sink: Option<Sink>,
+    /// Slot for the track that follows the current one on its sink without a gap.
+    follower: Follower,
+    control: Arc<SourceControl>,
+    path: Option<PathBuf>,
+    /// Length of the current track, if known; crossfades are timed against it.
+    duration: Option<Duration>,
+    next: Option<Next>,
+    /// The previous track fading out during a crossfade.
+    fading: Option<(Sink, Arc<SourceControl>)>,
+    state: State,
+    volume: f32,
+    position: Duration,
+    events: Vec<PlaybackEvent>,
+}
+
+impl Engine {
+    fn new(output: Box<dyn Output>) -> Self {
+        Self {
+            output,
+            sink: None,
+            follower: Follower::default(),
+            control: Arc::default(),
+            path: None,
+            duration: None,
+            next: None,
+            fading: None,
+            state: State::Stopped,
+            volume: 1.0,
+            position: Duration::ZERO,
+            events: Vec::new(),
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Load(path) => self.load(path, false),
+            Command::Cue { path, position } => {
+                self.load(path, true);
+                self.handle(Command::Seek(position));
+            }
+            Command::Preload { path, crossfade } => self.preload(path, crossfade),
+            Command::Play if self.state == State::Paused => {
+                self.with_sink(Sink::play);
+                self.state = State::Playing;
+                self.events.push(PlaybackEvent::Resumed);
+            }
+            Command::Pause if self.state == State::Playing => {
+                self.with_sink(Sink::pause);
+                self.state = State::Paused;
+                self.events.push(PlaybackEvent::Paused);
+            }
+            Command::Seek(to) if self.state != State::Stopped => {
+                self.control.seek(to);
+                self.position = to;
+                self.events.push(PlaybackEvent::Position(to));
+            }
+            Command::SetVolume(volume) => {
+                self.volume = volume;
+                self.with_sink(|sink| sink.set_volume(volume));
+            }
+            Command::Stop => {
+                self.stop();
+                self.events.push(PlaybackEvent::Stopped);
+            }
+            Command::Play | Command::Pause | Command::Seek(_) => {}
+        }
+    }
+
+    fn load(&mut self, path: PathBuf, paused: bool) {
+        self.stop();
+        self.control = Arc::default();
+        let sink = TrackSource::open(&path, self.control.clone()).and_then(|source| {
+            let sink = self.output.new_sink()?;
+            sink.set_volume(self.volume);
+            if paused {
+                sink.pause();
+            }
+            self.duration = source.total_duration();
+            self.follower = Follower::default();
+            sink.append(GaplessSource::new(source, self.follower.clone()));
+            Ok(sink)
+        });
+        match sink {
+            Ok(sink) => {
+                self.sink = Some(sink);
+                self.state = State::Playing;
+                self.events.push(PlaybackEvent::Started(path.clone()));
+                self.events.push(PlaybackEvent::Position(Duration::ZERO));
+                if paused {
+                    self.state = State::Paused;
+                    self.events.push(PlaybackEvent::Paused);
+                }
+                self.path = Some(path);
+            }
+            Err(err) => {
+                let message = format!("Не вдалося відтворити {}: {err}", path.display());
+                self.events.push(PlaybackEvent::Error(message));
+            }
+        }
+    }
+
+    fn preload(&mut self, path: Option<PathBuf>, crossfade: Duration) {
+        if let Some(next) = self.next.take() {
+            next.control.cancel();
+        }
+        self.set_follower(None);
+        let (Some(path), Some(_)) = (path, &self.sink) else {
+            return;
+        };
+        let control = Arc::<SourceControl>::default();
+        match TrackSource::open(&path, control.clone()) {
+            Ok(source) => {
+                let duration = source.total_duration();
+                // A short track should not spend most of its time fading
+                let crossfade = [self.duration, duration]
+                    .into_iter()
+                    .flatten()
+                    .fold(crossfade, |crossfade, length| crossfade.min(length / 2));
+                // Without the length of the current track there is no telling when to start
+                // fading, so it just follows without a gap.
+                let source = if crossfade.is_zero() || self.duration.is_none() {
+                    // The playing source switches to it right after its last sample, so the
+                    // output picks up a different sample rate or channel count exactly there.
+                    self.set_follower(Some(source));
+                    None
+                } else {
+                    Some(source)
+                };
+                self.next = Some(Next {
+                    path,
+                    control,
+                    source,
+                    duration,
+                    crossfade,
+                });
+            }
+            // Not worth an error yet: loading it for real when its turn comes reports it.
+            Err(err) => warn!("Failed to preload {}: {err}", path.display()),
+        }
+    }
+
+    fn stop(&mut self) {
+        for sink in [self.sink.take(), self.fading.take().map(|(sink, _)| sink)]
+            .into_iter()
+            .flatten()
+        {
+            sink.stop();
+        }
+        self.next = None;
+        self.state = State::Stopped;
+        self.position = Duration::ZERO;
+        self.duration = None;
+        self.path = None;
+    }
+
+    /// Reports the position and the end of the track, and hands over to the preloaded track
+    /// when it is due.
+    fn poll(&mut self) {
+        if self.state != State::Playing {
+            return;
+        }
+        let position = self.control.position();
+        if position != self.position {
+            self.position = position;
+            self.events.push(PlaybackEvent::Position(position));
+        }
+        if self
+            .fading
+            .as_ref()
+            .is_some_and(|(_, control)| control.is_finished())
+        {
+            self.fading = None;
+        }
+        let finished = self.control.is_finished();
+        let remaining = self
+            .duration
+            .map(|duration| duration.saturating_sub(position));
+        let due = |next: &mut Next| match next.source {
+            Some(_) => finished || remaining.is_some_and(|remaining| remaining <= next.crossfade),
+            None => finished,
+        };
+        match self.next.take_if(due) {
+            Some(next) => {
+                let fade = if finished {
+                    Duration::ZERO
+                } else {
+                    remaining.unwrap_or_default().min(next.crossfade)
+                };
+                self.continue_with(next, fade);
+            }
+            None if finished => {
+                let path = self.path.clone().unwrap_or_default();
+                self.stop();
+                self.events.push(PlaybackEvent::Finished(path));
+            }
+            None => {}
+        }
+    }
+
+    /// Makes the preloaded track the current one. If it was held back for a crossfade it starts
+    /// on a sink of its own now, fading in over `fade` while the old track fades out.
+    fn continue_with(&mut self, next: Next, fade: Duration) {
+        if let Some(source) = next.source {
+            let sink = match self.output.new_sink() {
+                Ok(sink) => sink,
+                Err(err) => {
+                    self.stop();
+                    let message = format!("Не вдалося відтворити {}: {err}", next.path.display());
+                    return self.events.push(PlaybackEvent::Error(message));
+                }
+            };
+            sink.set_volume(self.volume);
+            next.control.fade_in(fade);
+            self.control.fade_out(fade);
+            self.follower = Follower::default();
+            sink.append(GaplessSource::new(source, self.follower.clone()));
+            // The old track plays out on its own sink; dropping a sink would cut it off.
+            self.fading = self
+                .sink
+                .replace(sink)
+                .map(|sink| (sink, self.control.clone()));
+        }
+        self.control = next.control;
+        self.duration = next.duration;
+        self.position = self.control.position();
+        self.events
+            .push(PlaybackEvent::Continued(next.path.clone()));
+        self.events.push(PlaybackEvent::Position(self.position));
+        self.path = Some(next.path);
+    }
+
+    fn set_follower(&self, source: Option<TrackSource>) {
+        if let Ok(mut follower) = self.follower.lock() {
+            *follower = source;
+        }
+    }
+
+    /// Applies `f` to every sink that is audible: the current one and the one fading out.
+    fn with_sink(&self, f: impl Fn(&Sink)) {
+        let fading = self.fading.as_ref().map(|(sink, _)| sink);
+        for sink in self.sink.iter().chain(fading) {
+            f(sink);
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<PlaybackEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use pretty_assertions::assert_eq;
+    use rodio::queue::SourcesQueueOutput;
+
+    use super::*;
+    use crate::test_util::write_wav;
+
+    type Outputs = Arc<Mutex<Vec<SourcesQueueOutput<f32>>>>;
+
+    /// Sinks that are not connected to anything; the test pulls their samples.
+    struct IdleOutput(Outputs);
+
+    impl Output for IdleOutput {
+        fn new_sink(&mut self) -> Result<Sink> {
+            let (sink, output) = Sink::new_idle();
+            self.0.lock().unwrap().push(output);
+            Ok(sink)
+        }
+    }
+
+    fn idle_engine() -> (Engine, Outputs) {
+        let outputs = Outputs::default();
+        let engine = Engine::new(Box::new(IdleOutput(outputs.clone())));
+        (engine, outputs)
+    }
+
+    /// Plays `samples` samples of the newest sink, like the device would.
+    fn pull(outputs: &Outputs, samples: usize) {
+        let mut outputs = outputs.lock().unwrap();
+        let output = outputs.last_mut().unwrap();
+        output.by_ref().take(samples).for_each(drop);
+    }
+
+    #[test]
+    fn test_plays_a_track_to_the_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.wav");
+        write_wav(&path, [0; 8000]);
+        let (mut engine, outputs) = idle_engine();
+
+        engine.handle(Command::Load(path.clone()));
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Started(path.clone()),
+                PlaybackEvent::Position(Duration::ZERO)
+            ]
+        );
+
+        pull(&outputs, 4000);
+        engine.poll();
+        assert_eq!(
+            engine.take_events(),
+            vec![PlaybackEvent::Position(Duration::from_millis(500))]
+        );
+
+        pull(&outputs, 5000);
+        engine.poll();
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Position(Duration::from_secs(1)),
+                PlaybackEvent::Finished(path)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preloaded_track_follows_without_a_gap() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second, dropped) = (
+            dir.path().join("a.wav"),
+            dir.path().join("b.wav"),
+            dir.path().join("c.wav"),
+        );
+        for path in [&first, &second, &dropped] {
+            write_wav(path, [0; 8000]);
+        }
+        let (mut engine, outputs) = idle_engine();
+
+        engine.handle(Command::Load(first.clone()));
+        for path in [dropped, second.clone()] {
+            engine.handle(Command::Preload {
+                path: Some(path),
+                crossfade: Duration::ZERO,
+            });
+        }
+        engine.take_events();
+
+        // Both tracks come out of the one sink, back to back; the replaced preload is skipped
+        pull(&outputs, 8000 + 2000);
+        engine.poll();
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Position(Duration::from_secs(1)),
+                PlaybackEvent::Continued(second.clone()),
+                PlaybackEvent::Position(Duration::from_millis(250)),
+            ]
+        );
+        assert_eq!(outputs.lock().unwrap().len(), 1);
+
+        pull(&outputs, 6000 + 1);
+        engine.poll();
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Position(Duration::from_secs(1)),
+                PlaybackEvent::Finished(second)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crossfade_overlaps_the_tracks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("a.wav"), dir.path().join("b.wav"));
+        write_wav(&first, [0; 8000]);
+        write_wav(&second, [0; 8000]);
+        let (mut engine, outputs) = idle_engine();
+
+        engine.handle(Command::Load(first));
+        engine.handle(Command::Preload {
+            path: Some(second.clone()),
+            crossfade: Duration::from_millis(250),
+        });
+        engine.take_events();
+
+        pull(&outputs, 6000);
+        engine.poll();
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Position(Duration::from_millis(750)),
+                PlaybackEvent::Continued(second),
+                PlaybackEvent::Position(Duration::ZERO),
+            ]
+        );
+        // The second track got a sink of its own, the first one plays out on the old sink
+        assert_eq!(outputs.lock().unwrap().len(), 2);
+        engine.handle(Command::Pause);
+        assert!(engine.fading.as_ref().unwrap().0.is_paused());
+        engine.handle(Command::Play);
+
+        outputs.lock().unwrap()[0]
+            .by_ref()
+            .take(2001)
+            .for_each(drop);
+        engine.poll();
+        assert!(engine.fading.is_none());
+    }
+
+    #[test]
+    fn test_pause_seek_and_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.wav");
+        write_wav(&path, [0; 8000]);
+        let (mut engine, _outputs) = idle_engine();
+
+        engine.handle(Command::Load(path));
+        engine.take_events();
+        engine.handle(Command::Pause);
+        engine.handle(Command::Pause);
+        engine.handle(Command::Seek(Duration::from_millis(750)));
+        engine.handle(Command::Play);
+        engine.handle(Command::Stop);
+        engine.handle(Command::Seek(Duration::from_millis(100)));
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Paused,
+                PlaybackEvent::Position(Duration::from_millis(750)),
+                PlaybackEvent::Resumed,
+                PlaybackEvent::Stopped,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cue_loads_paused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.wav");
+        write_wav(&path, [0; 8000]);
+        let (mut engine, outputs) = idle_engine();
+
+        engine.handle(Command::Cue {
+            path: path.clone(),
+            position: Duration::from_millis(600),
+        });
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Started(path),
+                PlaybackEvent::Position(Duration::ZERO),
+                PlaybackEvent::Paused,
+                PlaybackEvent::Position(Duration::from_millis(600)),
+            ]
+        );
+        assert!(engine.sink.as_ref().unwrap().is_paused());
+
+        engine.handle(Command::Play);
+        pull(&outputs, 800);
+        engine.poll();
+        assert_eq!(
+            engine.take_events(),
+            vec![
+                PlaybackEvent::Resumed,
+                PlaybackEvent::Position(Duration::from_millis(700))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_file_is_reported() {
+        let (mut engine, _outputs) = idle_engine();
+        engine.handle(Command::Load(PathBuf::from("/nonexistent/song.mp3")));
+        let events = engine.take_events();
+        assert!(matches!(events.as_slice(), [PlaybackEvent::Error(_)]));
+        engine.poll();
+        assert!(engine.take_events().is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+//! Where the engine sends decoded audio: the sound card, nowhere, or a WAV file.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use color_eyre::Result;
+use rodio::{
+    OutputStream, OutputStreamHandle, Sink,
+    dynamic_mixer::{self, DynamicMixerController},
+};
+
+/// Sample format of the outputs that do not have a device to ask.
+const CHANNELS: u16 = 2;
+const SAMPLE_RATE: u32 = 44100;
+
+/// How much audio a headless output consumes at a time.
+const CHUNK: Duration = Duration::from_millis(10);
+
+/// An audio backend the engine plays through.
+pub trait Output {
+    /// A new sink connected to this output. The engine uses one per track.
+    fn new_sink(&mut self) -> Result<Sink>;
+}
+
+/// The default sound card.
+pub struct DeviceOutput {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+}
+
+impl DeviceOutput {
+    pub fn open() -> Result<Self> {
+        let (_stream, handle) = OutputStream::try_default()?;
+        Ok(Self { _stream, handle })
+    }
+}
+
+impl Output for DeviceOutput {
+    fn new_sink(&mut self) -> Result<Sink> {
+        Ok(Sink::try_new(&self.handle)?)
+    }
+}
+
+/// Pulls samples in real time like a sound card would and throws them away, so playback, position
+/// and track ends behave exactly as with a device.
+pub struct NullOutput(Clock);
+
+impl NullOutput {
+    pub fn new() -> Self {
+        Self(Clock::start(|_| {}))
+    }
+}
+
+impl Output for NullOutput {
+    fn new_sink(&mut self) -> Result<Sink> {
+        Ok(self.0.new_sink())
+    }
+}
+
+/// Like [`NullOutput`], but records everything that plays into a 32-bit float WAV file.
+pub struct WavOutput(Clock);
+
+impl WavOutput {
+    pub fn create(path: &Path) -> Result<Self> {
+        let spec = hound::WavSpec {
+            channels: CHANNELS,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::<BufWriter<File>>::create(path, spec)?;
+        Ok(Self(Clock::start(move |samples| {
+            for &sample in samples {
+                // A failed write would fail again; the file keeps what was written so far.
+                if writer.write_sample(sample).is_err() {
+                    break;
+                }
+            }
+            let _ = writer.flush();
+        })))
+    }
+}
+
+impl Output for WavOutput {
+    fn new_sink(&mut self) -> Result<Sink> {
+        Ok(self.0.new_sink())
+    }
+}
+
+/// A mixer drained in real time on its own thread, standing in for a sound card's callback.
+struct Clock {
+    mixer: Arc<DynamicMixerController<f32>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Clock {
+    /// `consume` receives every chunk of samples that was actually playing; silence while
+    /// nothing is loaded is skipped.
+    fn start(mut consume: impl FnMut(&[f32]) + Send + 'static) -> Self {
+        let (mixer, mut samples) = dynamic_mixer::mixer::<f32>(CHANNELS, SAMPLE_RATE);
+        let running = Arc::new(AtomicBool::new(true));
+        let keep_running = running.clone();
+        let thread = thread::spawn(move || {
+            let chunk_len =
+                (SAMPLE_RATE as f64 * CHUNK.as_secs_f64()) as usize * usize::from(CHANNELS);
+            let mut chunk = Vec::with_capacity(chunk_len);
+            let mut deadline = Instant::now();
+            while keep_running.load(Ordering::Relaxed) {
+                chunk.clear();
+                chunk.extend(samples.by_ref().take(chunk_len));
+                if !chunk.is_empty() {
+                    consume(&chunk);
+                }
+                // Sleeping until a deadline rather than for a fixed time keeps the pace from
+                // drifting.
+                deadline += CHUNK;
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            }
+        });
+        Self {
+            mixer,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    fn new_sink(&self) -> Sink {
+        let (sink, queue) = Sink::new_idle();
+        self.mixer.add(queue);
+        sink
+    }
+}
+
+impl Drop for Clock {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rodio::source::{SineWave, Source};
+
+    use super::*;
+
+    #[test]
+    fn test_wav_output_records_what_played() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.wav");
+        let mut output = WavOutput::create(&path).unwrap();
+
+        let sink = output.new_sink().unwrap();
+        sink.append(SineWave::new(440.0).take_duration(Duration::from_millis(200)));
+        sink.sleep_until_end();
+        drop(sink);
+        drop(output);
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, CHANNELS);
+        assert_eq!(reader.spec().sample_rate, SAMPLE_RATE);
+        let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+        // The sink may add some silence around the tone, depending on when the clock ticked
+        let first = samples.iter().position(|sample| *sample != 0.0).unwrap();
+        let last = samples.iter().rposition(|sample| *sample != 0.0).unwrap();
+        let frames = (last - first) / usize::from(CHANNELS);
+        // Resampling the 48 kHz tone may stretch it by a few frames
+        let expected = SAMPLE_RATE as usize / 5;
+        assert!(
+            frames.abs_diff(expected) <= expected / 100,
+            "recorded {frames} frames of tone for 200 ms"
+        );
+        let peak = samples
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.9, "the tone peaks at {peak}");
+    }
+}
@@ -0,0 +1,524 @@
+//! A rodio [`Source`] that decodes a track with symphonia and can seek while it plays.
+
+use std::{
+    f64::consts::FRAC_PI_2,
+    fs::File,
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use rodio::Source;
+use symphonia::{
+    core::{
+        audio::SampleBuffer,
+        codecs::{Decoder, DecoderOptions},
+        errors::Error,
+        formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+        io::MediaSourceStream,
+        meta::MetadataOptions,
+        probe::Hint,
+        units::{Time, TimeBase},
+    },
+    default::get_probe,
+};
+use tracing::warn;
+
+use super::codecs;
+
+const NO_SEEK: u64 = u64::MAX;
+
+/// Shared between a playing [`TrackSource`] and the player: the player asks for seeks and reads
+/// back how far the output got.
+#[derive(Debug)]
+pub struct SourceControl {
+    /// Requested seek target in milliseconds, [`NO_SEEK`] when there is none.
+    seek_to: AtomicU64,
+    /// Position of the last sample handed to the output, in microseconds.
+    position: AtomicU64,
+    /// Set once the last sample of the track has been played.
+    finished: AtomicBool,
+    /// Set when the track should end right away without being played, see [`Self::cancel`].
+    cancelled: AtomicBool,
+    /// Length of the fade in from the start of the track, in microseconds, 0 for none.
+    fade_in: AtomicU64,
+    /// Length of the fade out that ends with the track, in microseconds, 0 for none.
+    fade_out: AtomicU64,
+}
+
+impl Default for SourceControl {
+    fn default() -> Self {
+        Self {
+            seek_to: AtomicU64::new(NO_SEEK),
+            position: AtomicU64::new(0),
+            finished: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            fade_in: AtomicU64::new(0),
+            fade_out: AtomicU64::new(0),
+        }
+    }
+}
+
+impl SourceControl {
+    /// Asks the source to jump to `to`; it happens the next time the output pulls a sample.
+    pub fn seek(&self, to: Duration) {
+        self.seek_to.store(to.as_millis() as u64, Ordering::Relaxed);
+        // Report the target right away rather than the old position until the seek happens.
+        self.set_position(to);
+    }
+
+    /// How much of the track the output has consumed. Only samples that were actually pulled
+    /// count, so the position stands still while the output is paused or the app is suspended.
+    pub fn position(&self) -> Duration {
+        Duration::from_micros(self.position.load(Ordering::Relaxed))
+    }
+
+    /// Whether the source ran out of samples, i.e. the track played to its end.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Makes the source end as soon as the output reaches it. A source already appended to a sink
+    /// cannot be taken back out, so this is how a preloaded track is dropped.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Fades the track in over its first `length`.
+    pub fn fade_in(&self, length: Duration) {
+        self.fade_in
+            .store(length.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Fades the track out over its last `length`, so it is silent exactly when it ends.
+    pub fn fade_out(&self, length: Duration) {
+        self.fade_out
+            .store(length.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Gain of the sample at `position` in a track of length `total`. The fades follow
+    /// equal-power curves: a track fading out and one fading in over the same stretch keep the
+    /// overall loudness steady.
+    fn gain(&self, position: Duration, total: Option<Duration>) -> f32 {
+        let equal_power = |part: f64| (part.clamp(0.0, 1.0) * FRAC_PI_2).sin() as f32;
+        let mut gain = 1.0;
+        let fade_in = self.fade_in.load(Ordering::Relaxed);
+        if fade_in > 0 {
+            gain *= equal_power(position.as_micros() as f64 / fade_in as f64);
+        }
+        let fade_out = self.fade_out.load(Ordering::Relaxed);
+        if let Some(total) = total.filter(|_| fade_out > 0) {
+            let remaining = total.saturating_sub(position);
+            gain *= equal_power(remaining.as_micros() as f64 / fade_out as f64);
+        }
+        gain
+    }
+
+    fn set_position(&self, position: Duration) {
+        self.position
+            .store(position.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn take_seek(&self) -> Option<Duration> {
+        if self.seek_to.load(Ordering::Relaxed) == NO_SEEK {
+            return None;
+        }
+        let millis = self.seek_to.swap(NO_SEEK, Ordering::Relaxed);
+        (millis != NO_SEEK).then(|| Duration::from_millis(millis))
+    }
+}
+
+/// Decodes the default track of a file into interleaved `f32` samples.
+pub struct TrackSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+    buffer: Option<SampleBuffer<f32>>,
+    /// Samples of `buffer` that hold the current packet.
+    len: usize,
+    /// Next sample of `buffer` to play.
+    pos: usize,
+    /// After a seek, frames before this timestamp are decoded but not played.
+    skip_until: Option<u64>,
+    /// Position at the last seek (or sample rate change) and frames played since then.
+    base: Duration,
+    frames: u64,
+    /// Fade gain for the current frame.
+    gain: f32,
+    control: Arc<SourceControl>,
+}
+
+impl TrackSource {
+    /// Opens `path` and decodes its first packet, so the sample format is known up front.
+    pub fn open(path: &Path, control: Arc<SourceControl>) -> Result<Self> {
+        let file = File::open(path)?;
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
+        }
+        let probed = get_probe().format(
+            &hint,
+            mss,
+            &FormatOptions {
+                enable_gapless: true,
+                ..Default::default()
+            },
+            &MetadataOptions::default(),
+        )?;
+        let format = probed.format;
+        let track = format
+            .default_track()
+            .ok_or_else(|| eyre!("no audio track in {}", path.display()))?;
+        let decoder = codecs::registry().make(&track.codec_params, &DecoderOptions::default())?;
+        let params = track.codec_params.clone();
+
+        let mut source = Self {
+            track_id: track.id,
+            time_base: params.time_base,
+            channels: params
+                .channels
+                .map_or(2, |channels| channels.count() as u16),
+            sample_rate: params.sample_rate.unwrap_or(44100),
+            total_duration: codecs::n_frames(&params)
+                .zip(params.time_base)
+                .map(|(frames, time_base)| time_to_duration(time_base.calc_time(frames))),
+            format,
+            decoder,
+            buffer: None,
+            len: 0,
+            pos: 0,
+            skip_until: None,
+            base: Duration::ZERO,
+            frames: 0,
+            gain: 1.0,
+            control,
+        };
+        if !source.refill() {
+            return Err(eyre!("no audio could be decoded from {}", path.display()));
+        }
+        Ok(source)
+    }
+
+    /// Decodes packets until one yields samples to play. Returns `false` at the end of the track.
+    fn refill(&mut self) -> bool {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::DecodeError(err)) => {
+                    warn!("Skipping a broken packet: {err}");
+                    continue;
+                }
+                // End of file, or a read error that ends the track just the same.
+                Err(_) => return false,
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(err)) => {
+                    warn!("Skipping a broken packet: {err}");
+                    continue;
+                }
+                Err(_) => return false,
+            };
+
+            let spec = *decoded.spec();
+            let frames = decoded.frames();
+            let skip = match self.skip_until {
+                Some(target) if packet.ts < target => (target - packet.ts) as usize,
+                _ => 0,
+            };
+            if skip >= frames {
+                continue;
+            }
+            self.skip_until = None;
+
+            let buffer = match &mut self.buffer {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                    buffer
+                }
+                buffer => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+            self.len = buffer.samples().len();
+
+            if spec.rate != self.sample_rate {
+                self.base = self.played();
+                self.frames = 0;
+                self.sample_rate = spec.rate;
+            }
+            self.channels = spec.channels.count() as u16;
+            self.pos = skip * usize::from(self.channels);
+            return true;
+        }
+    }
+
+    fn seek(&mut self, to: Duration) {
+        let seek_to = SeekTo::Time {
+            time: Time::from(to),
+            track_id: Some(self.track_id),
+        };
+        match self.format.seek(SeekMode::Accurate, seek_to) {
+            Ok(seeked) => {
+                self.decoder.reset();
+                self.skip_until = Some(seeked.required_ts);
+                self.base = self.ts_to_duration(seeked.required_ts);
+                self.frames = 0;
+                self.control.set_position(self.base);
+                // Drop what is left of the packet decoded before the seek.
+                self.pos = self.len;
+            }
+            Err(err) => warn!("Failed to seek to {to:?}: {err}"),
+        }
+    }
+
+    fn played(&self) -> Duration {
+        self.base + Duration::from_secs_f64(self.frames as f64 / f64::from(self.sample_rate))
+    }
+
+    fn ts_to_duration(&self, ts: u64) -> Duration {
+        match self.time_base {
+            Some(time_base) => time_to_duration(time_base.calc_time(ts)),
+            None => Duration::from_secs_f64(ts as f64 / f64::from(self.sample_rate)),
+        }
+    }
+}
+
+fn time_to_duration(time: Time) -> Duration {
+    Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+}
+
+impl Iterator for TrackSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(to) = self.control.take_seek() {
+            self.seek(to);
+        }
+        if self.control.cancelled.load(Ordering::Relaxed) || self.pos >= self.len && !self.refill()
+        {
+            self.control.finished.store(true, Ordering::Relaxed);
+            return None;
+        }
+        let sample = self.buffer.as_ref()?.samples()[self.pos];
+        if self.pos.is_multiple_of(usize::from(self.channels)) {
+            self.frames += 1;
+            let played = self.played();
+            self.control.set_position(played);
+            self.gain = self.control.gain(played, self.total_duration);
+        }
+        self.pos += 1;
+        // Decode the next packet right away: an output that asks for the frame length now must
+        // get the length (and format) of that packet, and 0 only at the end of the track
+        if self.pos >= self.len {
+            self.refill();
+        }
+        Some(sample * self.gain)
+    }
+}
+
+impl Source for TrackSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        // The sample format can only change between packets.
+        if self.control.cancelled.load(Ordering::Relaxed) {
+            return Some(0);
+        }
+        Some(self.len - self.pos)
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+/// The track waiting to follow a [`GaplessSource`], filled in by the engine.
+pub type Follower = Arc<Mutex<Option<TrackSource>>>;
+
+/// Plays a track and then, without a gap, the one waiting in its [`Follower`] slot.
+///
+/// Appending the next track to the sink is not enough when the formats differ: when a source
+/// ends, rodio's queue assumes a 512 sample frame in the old format before it switches, so the
+/// new track would start at the wrong sample rate or channel count.
+pub struct GaplessSource {
+    current: TrackSource,
+    follower: Follower,
+}
+
+impl GaplessSource {
+    pub fn new(current: TrackSource, follower: Follower) -> Self {
+        Self { current, follower }
+    }
+
+    /// Switches to the follower as soon as the current track has no samples left, before the
+    /// output asks for the next frame.
+    fn follow(&mut self) {
+        if self.current.current_frame_len() != Some(0) {
+            return;
+        }
+        let next = self.follower.lock().ok().and_then(|mut next| next.take());
+        if let Some(next) = next {
+            // Let the ended track see its end, so the engine learns of the handover
+            let _ = self.current.next();
+            self.current = next;
+        }
+    }
+}
+
+impl Iterator for GaplessSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.current.next();
+        self.follow();
+        sample.or_else(|| self.current.next())
+    }
+}
+
+impl Source for GaplessSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.current.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.current.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.current.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_util::{write_wav, write_wav_as};
+
+    /// Two seconds of a ramp, so every sample tells where it came from.
+    const RAMP: std::ops::Range<i16> = 0..16000;
+
+    #[test]
+    fn test_decodes_every_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.wav");
+        write_wav(&path, RAMP);
+
+        let control = Arc::new(SourceControl::default());
+        let mut source = TrackSource::open(&path, control.clone()).unwrap();
+        assert_eq!((source.channels(), source.sample_rate()), (1, 8000));
+        assert_eq!(source.total_duration(), Some(Duration::from_secs(2)));
+        assert_eq!(control.position(), Duration::ZERO);
+
+        assert_eq!(source.by_ref().take(4000).count(), 4000);
+        assert_eq!(control.position(), Duration::from_millis(500));
+        assert!(!control.is_finished());
+
+        assert_eq!(source.count(), 12000);
+        assert_eq!(control.position(), Duration::from_secs(2));
+        assert!(control.is_finished());
+    }
+
+    #[test]
+    fn test_fades_follow_equal_power_curves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.wav");
+        write_wav(&path, RAMP);
+
+        let control = Arc::new(SourceControl::default());
+        let source = TrackSource::open(&path, control.clone()).unwrap();
+        control.fade_in(Duration::from_secs(1));
+        control.fade_out(Duration::from_secs(1));
+        let gains: Vec<f32> = source
+            .enumerate()
+            .map(|(i, sample)| sample * 32768.0 / i.max(1) as f32)
+            .collect();
+
+        // Halfway through the fade in (and in the one out) both tracks play at -3 dB
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((gains[4000 - 1] - half).abs() < 1e-3);
+        assert!((gains[8000 - 1] - 1.0).abs() < 1e-3);
+        assert!((gains[12000 - 1] - half).abs() < 1e-3);
+        assert!(gains[16000 - 1].abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_seek_jumps_to_the_exact_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.wav");
+        write_wav(&path, RAMP);
+
+        let control = Arc::new(SourceControl::default());
+        let mut source = TrackSource::open(&path, control.clone()).unwrap();
+        source.next();
+        assert_eq!(control.position(), Duration::from_micros(125));
+        control.seek(Duration::from_millis(1500));
+
+        let sample = source.next().unwrap();
+        assert_eq!((sample * 32768.0).round() as i32, 12000);
+        assert_eq!(
+            control.position(),
+            Duration::from_millis(1500) + Duration::from_micros(125)
+        );
+        assert_eq!(source.count(), 16000 - 12000 - 1);
+    }
+
+    #[test]
+    fn test_gapless_switches_format_at_the_track_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("mono.wav"), dir.path().join("stereo.wav"));
+        // Half a second of positive mono 8 kHz samples, then negative stereo 16 kHz ones
+        write_wav(&first, 1..=4000);
+        write_wav_as(&second, 2, 16000, [-1000; 16000]);
+
+        let follower = Follower::default();
+        let source = TrackSource::open(&first, Arc::default()).unwrap();
+        let (sink, mut output) = rodio::Sink::new_idle();
+        sink.append(GaplessSource::new(source, follower.clone()));
+        *follower.lock().unwrap() = Some(TrackSource::open(&second, Arc::default()).unwrap());
+
+        // Read frame by frame like rodio's format converter, noting the format announced for
+        // every sample. The queue starts on an empty placeholder, so the first sample comes
+        // before the first frame.
+        let mut samples = vec![(output.next().unwrap(), (1, 8000))];
+        while samples.len() < 4000 + 16000 {
+            let format = (output.channels(), output.sample_rate());
+            let len = output.current_frame_len().unwrap();
+            samples.extend(output.by_ref().take(len).map(|sample| (sample, format)));
+        }
+
+        assert_eq!(samples.len(), 4000 + 16000);
+        let (mono, stereo) = samples.split_at(4000);
+        assert!(
+            mono.iter()
+                .all(|&(sample, format)| sample > 0.0 && format == (1, 8000))
+        );
+        assert!(
+            stereo
+                .iter()
+                .all(|&(sample, format)| sample < 0.0 && format == (2, 16000))
+        );
+    }
+}
@@ -1,6 +1,11 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
-use crate::config::{get_config_dir, get_data_dir};
+use crate::{
+    config::{OutputBackend, get_config_dir, get_data_dir},
+    library::scanner::SUPPORTED_FORMATS,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
@@ -12,6 +17,22 @@ pub struct Cli {
     /// Frame rate, i.e. number of frames per second
     #[arg(short, long, value_name = "FLOAT", default_value_t = 60.0)]
     pub frame_rate: f64,
+
+    /// Music directory to scan instead of `library.roots`; may be given several times
+    #[arg(short, long = "music-dir", value_name = "PATH")]
+    pub music_dir: Vec<PathBuf>,
+
+    /// Ignore the library cache and probe every file again
+    #[arg(long)]
+    pub rescan: bool,
+
+    /// Audio output instead of `player.output`: device, null or wav:PATH
+    #[arg(long, value_name = "BACKEND")]
+    pub output: Option<OutputBackend>,
+
+    /// Start with an empty queue instead of restoring the last session
+    #[arg(long)]
+    pub no_resume: bool,
 }
 
 const VERSION_MESSAGE: &str = concat!(
@@ -29,6 +50,7 @@ pub fn version() -> String {
     // let current_exe_path = PathBuf::from(clap::crate_name!()).display().to_string();
     let config_dir_path = get_config_dir().display().to_string();
     let data_dir_path = get_data_dir().display().to_string();
+    let formats = SUPPORTED_FORMATS.join(", ");
 
     format!(
         "\
@@ -37,6 +59,8 @@ pub fn version() -> String {
 Authors: {author}
 
 Config directory: {config_dir_path}
-Data directory: {data_dir_path}"
+Data directory: {data_dir_path}
+
+Audio formats: {formats}"
     )
 }
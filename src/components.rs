@@ -11,7 +11,10 @@ use crate::{action::Action, config::Config, tui::Event};
 pub mod fps;
 pub mod home;
 pub mod player;
+pub mod prompt;
+pub mod queue_view;
 pub mod song_list;
+pub mod stats_view;
 
 /// `Component` is a trait that represents a visual and interactive element of the user interface.
 ///
@@ -1,296 +1,764 @@
-use std::{fs, io, path::Path};
-
-use symphonia::core::{
-    codecs::DecoderOptions,
-    formats::FormatOptions,
-    meta::MetadataOptions,
-    probe::Hint,
-    io::MediaSourceStream,
-};
-use symphonia::default::{get_codecs, get_probe};
-
-
+use std::{ops::ControlFlow, path::PathBuf};
 
-use color_eyre::Result;
+use color_eyre::{Result, eyre::eyre};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders,List, ListItem, ListState},
-    text::{Line, Span, },
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
 };
-
-use tokio::sync::mpsc::UnboundedSender;
-
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
+use tracing::{info, warn};
 
 use crate::{
-    action::Action, 
-    components::{Component, player::Player},  
-    config::Config
+    action::Action,
+    app::Mode,
+    components::{
+        Component,
+        player::{self, Player},
+        prompt::{Prompt, PromptEvent},
+        queue_view::QueueView,
+        song_list::{SongList, View},
+        stats_view::StatsView,
+    },
+    config::{self, Config},
+    history::{
+        self, History, HistoryEntry,
+        stats::{self, Stats},
+        trends::Trend,
+    },
+    library::{
+        Library, Track,
+        cache::{self, LibraryCache},
+        favorites::{self, Favorites},
+        playlists::{self, ImportReport, Playlists},
+        scanner::{self, ScanEvent, ScanReport},
+        watcher::LibraryWatcher,
+    },
+    session::{self, Session},
 };
 
+/// Index of "Тренди" in the sidebar; the trends are listed right below it.
+const TRENDS: usize = 0;
+/// Index of "Улюблені" in the sidebar.
+const FAVORITES: usize = TRENDS + Trend::ALL.len() + 1;
+/// Index of "Списки відтворення" in the sidebar; the playlists are listed right below it.
+const PLAYLISTS: usize = FAVORITES + 1;
+
+/// What the open prompt is asking for.
+#[derive(Debug, Clone)]
+enum PromptKind {
+    Seek,
+    CreatePlaylist,
+    RenamePlaylist(usize),
+    DeletePlaylist(usize),
+    AddToPlaylist(Box<Track>),
+    ImportPlaylist,
+    ExportPlaylist(usize),
+    ClearQueue,
+}
+
 #[derive(Default)]
 pub struct Home {
-    player: Player,  
+    player: Player,
+    song_list: SongList,
+    queue_view: QueueView,
     command_tx: Option<UnboundedSender<Action>>,
     config: Config,
     selected_widget: usize,
     selected_index: usize,
-    selected_song_index: usize,
     list_items: Vec<ListItem<'static>>,
-    song_items: Vec<(String, String, u64)>,
+    library: Library,
+    favorites: Favorites,
+    playlists: Playlists,
+    history: History,
+    /// Playlist the last track was added to, offered again next time.
+    last_playlist: Option<String>,
+    /// Folder of the last imported or exported playlist file.
+    playlist_dir: Option<PathBuf>,
+    /// Entries the last import could not find, shown until dismissed with Esc.
+    import_report: Option<ImportReport>,
+    scan_report: ScanReport,
+    show_scan_report: bool,
+    /// Open statistics screen; computed when opened and not updated while shown.
+    stats_view: Option<StatsView>,
+    prompt: Option<(Prompt, PromptKind)>,
+    mode: Mode,
+    rescan: bool,
+    resume: bool,
+    watcher: Option<LibraryWatcher>,
 }
 
 impl Home {
     pub fn new() -> Self {
-        let list = Self::get_audio_files("local_music").unwrap_or_default();
-
         Self {
-            player: Player::new(),  
-            //  is_playing: false,
-            // volume: 1.0,
+            player: Player::new(),
+            song_list: SongList::new(),
+            queue_view: QueueView::new(),
             selected_widget: 0,
             selected_index: 0,
-            selected_song_index: 0,
-            list_items: vec![
-                ListItem::new(" Тренди"),
-                ListItem::new(" Улюблені"),
-                ListItem::new(" Списки відтворення"),
-            ],
-            song_items: list,
+            list_items: std::iter::once(" Тренди".to_string())
+                .chain(Trend::ALL.map(|trend| format!("   {}", trend.label())))
+                .chain([" Улюблені".to_string(), " Списки відтворення".to_string()])
+                .map(ListItem::new)
+                .collect(),
             ..Default::default()
         }
     }
 
-    
+    /// Ignore the library cache on startup and probe every file again.
+    pub fn rescan(mut self, rescan: bool) -> Self {
+        self.rescan = rescan;
+        self
+    }
 
-    fn border_style(&self, index: usize) -> Style {
-        if self.selected_widget == index {
-            Style::default().fg(Color::White)
-        } else {
-            Style::default().fg(Color::DarkGray)
+    /// Restore the queue and the paused track from the last session on startup.
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    fn session_path(&self) -> PathBuf {
+        self.config.config.data_dir.join(session::FILE_NAME)
+    }
+
+    fn restore_session(&mut self) {
+        let session = match Session::load(&self.session_path()) {
+            Ok(Some(session)) => session,
+            Ok(None) => return,
+            Err(err) => return warn!("Ignoring saved session: {err}"),
+        };
+        let queue = self.queue_view.queue_mut();
+        queue.restore(session.tracks, session.current);
+        if let Some(track) = queue.current() {
+            self.player.cue(track, session.position);
         }
     }
 
+    fn favorites_path(&self) -> std::path::PathBuf {
+        self.config.config.data_dir.join(favorites::FILE_NAME)
+    }
 
+    fn load_favorites(&mut self) {
+        self.favorites = Favorites::load(&self.favorites_path()).unwrap_or_else(|err| {
+            warn!("Ignoring saved favorites: {err}");
+            Favorites::default()
+        });
+        self.song_list.set_favorites(self.favorites.clone());
+    }
 
-pub fn get_audio_files(path: &str) -> io::Result<Vec<(String, String, u64)>> {
-    let mut results = vec![];
+    /// The track under the cursor of the focused pane, or the one playing in the player pane.
+    fn focused_track(&self) -> Option<&Track> {
+        match self.selected_widget {
+            2 => self.queue_view.selected_track(),
+            3 => self.player.current_track(),
+            _ => self.song_list.selected_track(),
+        }
+    }
 
-    for entry in fs::read_dir(Path::new(path))? {
-        let entry = entry?;
-        let path = entry.path();
+    fn toggle_favorite(&mut self) {
+        let Some(track) = self.focused_track().cloned() else {
+            return;
+        };
+        let liked = self.favorites.toggle(&track);
+        info!(
+            "{} {}",
+            if liked { "Liked" } else { "Unliked" },
+            track.path.display()
+        );
+        if let Err(err) = self.favorites.save(&self.favorites_path()) {
+            warn!("Failed to save favorites: {err}");
+        }
+        self.song_list.set_favorites(self.favorites.clone());
+    }
 
-        // Перевірка на розширення
-        let ext = path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("")
-            .to_lowercase();
+    fn playlists_path(&self) -> PathBuf {
+        self.config.config.data_dir.join(playlists::FILE_NAME)
+    }
 
-        // Підтримувані формати
-        let supported = ["mp3", "flac", "wav", "aac", "m4a"];
+    fn load_playlists(&mut self) {
+        self.playlists = Playlists::load(&self.playlists_path()).unwrap_or_else(|err| {
+            warn!("Ignoring saved playlists: {err}");
+            Playlists::default()
+        });
+    }
 
-        if !supported.contains(&ext.as_str()) {
-            continue;
+    fn save_playlists(&self) {
+        if let Err(err) = self.playlists.save(&self.playlists_path()) {
+            warn!("Failed to save playlists: {err}");
         }
+    }
 
-        let file = std::fs::File::open(&path)?;
-        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    fn history_path(&self) -> PathBuf {
+        self.config.config.data_dir.join(history::FILE_NAME)
+    }
 
-        let mut hint = Hint::new();
-        hint.with_extension(&ext);
+    fn load_history(&mut self) {
+        self.history = History::load(&self.history_path()).unwrap_or_else(|err| {
+            warn!("Ignoring the play history: {err}");
+            History::default()
+        });
+    }
 
-        let probed = get_probe()
-            .format(
-                &hint,
-                mss,
-                &FormatOptions::default(),
-                &MetadataOptions::default(),
-            )
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to parse audio"))?;
+    fn record_play(&mut self, entry: HistoryEntry) {
+        let path = self.history_path();
+        if let Err(err) = self.history.append(&path, entry) {
+            warn!("Failed to write the play history: {err}");
+        }
+    }
 
-        let mut format = probed.format;
-        let track = format.default_track().ok_or_else(|| {
-            io::Error::new(io::ErrorKind::Other, "No default track found")
-        })?;
+    fn toggle_stats(&mut self) {
+        if self.stats_view.take().is_some() {
+            return;
+        }
+        let now = history::now();
+        let stats = Stats::compute(
+            &self.history,
+            self.library.tracks(),
+            now,
+            stats::utc_offset(now),
+        );
+        self.stats_view = Some(StatsView::new(stats));
+    }
+
+    fn selected_trend(&self) -> Option<Trend> {
+        let index = self.selected_index.checked_sub(TRENDS + 1)?;
+        Trend::ALL.get(index).copied()
+    }
+
+    /// The playlist under the sidebar cursor, which is also the one the song list shows.
+    fn selected_playlist(&self) -> Option<usize> {
+        self.selected_index
+            .checked_sub(PLAYLISTS + 1)
+            .filter(|&index| index < self.playlists.playlists().len())
+    }
 
-        let decoder = get_codecs()
-            .make(&track.codec_params, &DecoderOptions::default())
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to create decoder"))?;
+    fn sidebar_len(&self) -> usize {
+        self.list_items.len() + self.playlists.playlists().len()
+    }
 
-        // Отримуємо тривалість
-        let duration = if let (Some(frames), Some(rate)) =
-            (track.codec_params.n_frames, track.codec_params.sample_rate)
-        {
-            frames as u64 / rate as u64
+    /// Shows in the song list what the sidebar cursor points at.
+    fn refresh_view(&mut self) {
+        let view = if let Some(index) = self.selected_playlist() {
+            View::Playlist {
+                name: self.playlists.playlists()[index].name.clone(),
+                tracks: self.playlists.resolve(index, self.library.tracks()),
+            }
+        } else if let Some(trend) = self.selected_trend() {
+            View::Trend {
+                trend,
+                tracks: trend.tracks(&self.history, self.library.tracks(), history::now()),
+            }
+        } else if self.selected_index == FAVORITES {
+            View::Favorites
         } else {
-            0
+            View::All
         };
+        self.song_list.set_view(view);
+    }
 
-        let name = path
-            .file_stem()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string();
+    /// Enter in the sidebar on a playlist: the playlist replaces the queue.
+    fn play_playlist(&mut self, index: usize) {
+        let tracks = self.playlists.resolve(index, self.library.tracks());
+        if let Some(track) = self.queue_view.queue_mut().replace(tracks, 0) {
+            self.player.play_sample(track);
+        }
+    }
 
-        results.push((name, ext.clone(), duration));
+    fn enqueue_playlist(&mut self, index: usize) {
+        for track in self.playlists.resolve(index, self.library.tracks()) {
+            self.queue_view.queue_mut().enqueue(track);
+        }
     }
 
-    Ok(results)
-}
+    /// Removes or moves an entry of the playlist shown in the song list.
+    fn edit_playlist(&mut self, index: usize, code: KeyCode) {
+        let entry = self.song_list.selected();
+        let moved = match code {
+            KeyCode::Char('x') => self.playlists.remove_entry(index, entry).map(|_| entry),
+            KeyCode::Char('[') => self.playlists.move_entry(index, entry, true),
+            KeyCode::Char(']') => self.playlists.move_entry(index, entry, false),
+            _ => None,
+        };
+        let Some(selected) = moved else {
+            return;
+        };
+        self.save_playlists();
+        self.refresh_view();
+        self.song_list.select(selected);
+    }
 
-    fn next_widget(&mut self) {
-        self.selected_widget = (self.selected_widget + 1) % 3;
+    fn open_prompt(&mut self, prompt: Prompt, kind: PromptKind) {
+        self.prompt = Some((prompt, kind));
+        self.set_mode(Mode::Input);
     }
 
-    
-    fn format_duration(&self, secs: &u64) -> String {
-        let minutes = secs / 60;
-        let seconds = secs % 60;
-        format!("{:02}:{:02}", minutes, seconds)
+    fn open_playlist_prompt(&mut self, code: KeyCode) {
+        let selected = self.selected_playlist();
+        let name = |index: usize| self.playlists.playlists()[index].name.clone();
+        let (prompt, kind) = match (code, selected) {
+            (KeyCode::Char('c'), _) => (
+                Prompt::new("Новий список").hint("Назва списку, Esc — скасувати"),
+                PromptKind::CreatePlaylist,
+            ),
+            (KeyCode::F(2), Some(index)) => (
+                Prompt::new("Перейменувати список")
+                    .hint("Нова назва, Esc — скасувати")
+                    .input(name(index)),
+                PromptKind::RenamePlaylist(index),
+            ),
+            (KeyCode::Delete, Some(index)) => (
+                Prompt::new(format!("Видалити «{}»?", name(index)))
+                    .hint("Enter — видалити, Esc — скасувати"),
+                PromptKind::DeletePlaylist(index),
+            ),
+            (KeyCode::Char('i'), _) => (
+                Prompt::new("Імпортувати список")
+                    .hint("Шлях до .m3u, .m3u8, .pls або .xspf")
+                    .input(format!("{}/", self.playlist_dir().display())),
+                PromptKind::ImportPlaylist,
+            ),
+            (KeyCode::Char('w'), Some(index)) => (
+                Prompt::new(format!("Експортувати «{}»", name(index)))
+                    .hint("Формат визначає розширення: .m3u8, .pls або .xspf")
+                    .input(
+                        self.playlist_dir()
+                            .join(format!("{}.m3u8", name(index)))
+                            .display()
+                            .to_string(),
+                    ),
+                PromptKind::ExportPlaylist(index),
+            ),
+            _ => return,
+        };
+        self.open_prompt(prompt, kind);
     }
 
-fn next_song(&mut self) {
-    if self.selected_song_index + 1 < self.song_items.len() {
-          self.player.finished = false; // скидаємо
-        self.selected_song_index += 1;
-    } else {
-        self.selected_song_index = 0; // або залишити на останньому
+    fn open_add_to_playlist_prompt(&mut self) {
+        let Some(track) = self.focused_track().cloned() else {
+            return;
+        };
+        let prompt = Prompt::new(format!("Додати «{}» до списку", track.title()))
+            .hint("Назва списку; якщо такого нема, його буде створено")
+            .input(self.last_playlist.clone().unwrap_or_default());
+        self.open_prompt(prompt, PromptKind::AddToPlaylist(Box::new(track)));
+    }
+
+    /// Where playlist files are looked for first: the folder used last time, or the first
+    /// library root.
+    fn playlist_dir(&self) -> PathBuf {
+        self.playlist_dir
+            .clone()
+            .or_else(|| self.config.library.roots.first().cloned())
+            .unwrap_or_default()
     }
-     self.player.play_sample(  
-                    &self.song_items[self.selected_song_index].0,
-                    &self.song_items[self.selected_song_index].1,
-                    &self.song_items[self.selected_song_index].2,
-                )
-}
 
-fn prev_song(&mut self) {
-    if self.selected_song_index > 0 {
-          self.player.finished = false; // скидаємо
-        self.selected_song_index -= 1;
-    } else {
-        self.selected_song_index = self.song_items.len() - 1; // або залишити на першому
-    }
-     self.player.play_sample(  
-                    &self.song_items[self.selected_song_index].0,
-                    &self.song_items[self.selected_song_index].1,
-                    &self.song_items[self.selected_song_index].2,
+    fn handle_prompt(&mut self, key: KeyEvent) {
+        let Some((prompt, kind)) = &mut self.prompt else {
+            return;
+        };
+        let kind = kind.clone();
+        match prompt.handle_key(key) {
+            PromptEvent::Pending => return,
+            PromptEvent::Submit(input) => {
+                if let Err(err) = self.submit_prompt(kind, &input) {
+                    // Залишаємо підказку відкритою, щоб можна було виправити
+                    if let Some((prompt, _)) = &mut self.prompt {
+                        prompt.set_error(format!("{err:#}"));
+                    }
+                    return;
+                }
+            }
+            PromptEvent::Cancel => {}
+        }
+        self.prompt = None;
+        self.set_mode(Mode::Home);
+    }
+
+    fn submit_prompt(&mut self, kind: PromptKind, input: &str) -> Result<()> {
+        match kind {
+            PromptKind::Seek => {
+                let to = player::parse_timestamp(input)
+                    .ok_or_else(|| eyre!("Не схоже на час: {input}"))?;
+                self.player.seek(to);
+                return Ok(());
+            }
+            PromptKind::CreatePlaylist => {
+                let index = self.playlists.create(input)?;
+                self.selected_index = PLAYLISTS + 1 + index;
+            }
+            PromptKind::RenamePlaylist(index) => self.playlists.rename(index, input)?,
+            PromptKind::DeletePlaylist(index) => {
+                self.playlists.delete(index);
+                self.selected_index = self.selected_index.min(self.sidebar_len() - 1);
+            }
+            PromptKind::AddToPlaylist(track) => {
+                let index = match self.playlists.position(input.trim()) {
+                    Some(index) => index,
+                    None => self.playlists.create(input)?,
+                };
+                self.playlists.add(index, [track.as_ref()]);
+                self.last_playlist = Some(self.playlists.playlists()[index].name.clone());
+            }
+            PromptKind::ImportPlaylist => {
+                let file = config::expand_path(input.trim());
+                let report = self.playlists.import(
+                    &file,
+                    &self.config.library.roots,
+                    self.library.tracks(),
+                )?;
+                let name = &self.playlists.playlists()[report.index].name;
+                info!(
+                    "Imported {} as {name:?}, {} entries not found",
+                    file.display(),
+                    report.unresolved.len()
                 );
+                self.selected_index = PLAYLISTS + 1 + report.index;
+                self.playlist_dir = file.parent().map(PathBuf::from);
+                self.import_report = Some(report).filter(|report| !report.unresolved.is_empty());
             }
-    
-fn handle_list_navigation(&mut self, code: KeyCode) {
-    match self.selected_widget {
-        0 => {
-            let max = self.list_items.len();
-            if let KeyCode::Up = code {
-                if self.selected_index > 0 {
-                    self.selected_index -= 1;
-                }
-            } else if let KeyCode::Down = code {
-                if self.selected_index + 1 < max {
-                    self.selected_index += 1;
-                }
+            PromptKind::ExportPlaylist(index) => {
+                let file = config::expand_path(input.trim());
+                self.playlists.export(index, &file, self.library.tracks())?;
+                info!("Exported playlist {index} to {}", file.display());
+                self.playlist_dir = file.parent().map(PathBuf::from);
+                return Ok(());
+            }
+            PromptKind::ClearQueue => {
+                self.queue_view.clear();
+                return Ok(());
             }
         }
-        1 => {
-            let max = self.song_items.len();
-            if let KeyCode::Up = code {
-                if self.selected_song_index > 0 {
-                    self.selected_song_index -= 1;
+        self.save_playlists();
+        self.refresh_view();
+        Ok(())
+    }
+
+    fn save_session(&self) {
+        let queue = self.queue_view.queue();
+        let session = Session {
+            tracks: queue.tracks().to_vec(),
+            current: queue.current_index(),
+            position: self.player.position(),
+        };
+        if let Err(err) = session.save(&self.session_path()) {
+            warn!("Failed to save the session: {err}");
+        }
+    }
+
+    fn border_style(&self, index: usize) -> Style {
+        if self.selected_widget == index {
+            Style::default().fg(Color::White)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        }
+    }
+
+    /// Scans the configured library roots on a blocking task; tracks arrive as
+    /// [`Action::LibraryTrackAdded`] while the UI keeps running.
+    fn start_scan(&mut self) {
+        let Some(tx) = self.command_tx.clone() else {
+            return;
+        };
+        let roots = self.config.library.roots.clone();
+        let cache_path = self.config.config.data_dir.join(cache::FILE_NAME);
+        let rescan = self.rescan;
+        let (cache_tx, cache_rx) = oneshot::channel();
+        match LibraryWatcher::start(&roots, cache_rx, cache_path.clone(), tx.clone()) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(err) => warn!("Library folders will not be watched: {err}"),
+        }
+        self.song_list.set_scan_progress(Some((0, 0)));
+        tokio::task::spawn_blocking(move || {
+            let cache = if rescan {
+                LibraryCache::default()
+            } else {
+                LibraryCache::load(&cache_path).unwrap_or_else(|err| {
+                    warn!("Ignoring library cache {}: {err}", cache_path.display());
+                    LibraryCache::default()
+                })
+            };
+            let fresh = scanner::scan(&roots, &cache, |event| {
+                let action = match event {
+                    ScanEvent::Track(track) => Action::LibraryTrackAdded(Box::new(track)),
+                    ScanEvent::Progress { scanned, total } => {
+                        Action::LibraryScanProgress(scanned, total)
+                    }
+                    ScanEvent::Done(report) => Action::LibraryScanDone(report),
+                };
+                // The receiver is gone once the app quits, so there is no point to go on.
+                match tx.send(action) {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(_) => ControlFlow::Break(()),
                 }
-            } else if let KeyCode::Down = code {
-                if self.selected_song_index + 1 < max {
-                    self.selected_song_index += 1;
+            });
+            if let Some(fresh) = fresh {
+                if let Err(err) = fresh.save(&cache_path) {
+                    warn!("Failed to save library cache: {err}");
                 }
+                // From now on the watcher keeps the cache up to date.
+                let _ = cache_tx.send(fresh);
             }
+        });
+    }
+
+    fn next_widget(&mut self) {
+        self.selected_widget = (self.selected_widget + 1) % 4;
+        self.song_list.set_focused(self.selected_widget == 1);
+        self.queue_view.set_focused(self.selected_widget == 2);
+    }
+
+    /// Enter in the song list: the whole list becomes the queue, starting at the selection.
+    fn play_selected(&mut self) {
+        let tracks = self.song_list.tracks().cloned().collect();
+        let track = self
+            .queue_view
+            .queue_mut()
+            .replace(tracks, self.song_list.selected());
+        if let Some(track) = track {
+            self.player.play_sample(track);
+        }
+    }
+
+    /// Enter in the queue pane: jump to the track under the queue cursor.
+    fn play_queued(&mut self) {
+        let cursor = self.queue_view.cursor();
+        if let Some(track) = self.queue_view.queue_mut().jump(cursor) {
+            self.player.play_sample(track);
         }
-        2 => {
-            match code {
+    }
+
+    fn next_song(&mut self) {
+        if let Some(track) = self.queue_view.queue_mut().advance() {
+            self.player.play_sample(track);
+        }
+    }
+
+    fn prev_song(&mut self) {
+        if let Some(track) = self.queue_view.queue_mut().previous() {
+            self.player.play_sample(track);
+        }
+    }
+
+    fn enqueue_selected(&mut self, next: bool) {
+        if let (0, Some(index), false) = (self.selected_widget, self.selected_playlist(), next) {
+            return self.enqueue_playlist(index);
+        }
+        let Some(track) = self.song_list.selected_track().cloned() else {
+            return;
+        };
+        let queue = self.queue_view.queue_mut();
+        if next {
+            queue.play_next(track);
+        } else {
+            queue.enqueue(track);
+        }
+    }
+
+    fn handle_list_navigation(&mut self, code: KeyCode) {
+        match self.selected_widget {
+            0 => match code {
+                KeyCode::Up if self.selected_index > 0 => {
+                    self.selected_index -= 1;
+                    self.refresh_view();
+                }
+                KeyCode::Down if self.selected_index + 1 < self.sidebar_len() => {
+                    self.selected_index += 1;
+                    self.refresh_view();
+                }
+                KeyCode::Char('c' | 'i' | 'w') | KeyCode::F(2) | KeyCode::Delete => {
+                    self.open_playlist_prompt(code)
+                }
+                _ => {}
+            },
+            1 => match code {
+                KeyCode::Up => self.song_list.select_previous(),
+                KeyCode::Down => self.song_list.select_next(),
+                KeyCode::Char('x' | '[' | ']') => {
+                    if let Some(index) = self.selected_playlist() {
+                        self.edit_playlist(index, code);
+                    }
+                }
+                _ => {}
+            },
+            2 => match code {
+                KeyCode::Up => self.queue_view.select_previous(),
+                KeyCode::Down => self.queue_view.select_next(),
+                KeyCode::Char('x') => self.queue_view.remove_selected(),
+                KeyCode::Char('[') => self.queue_view.move_selected(true),
+                KeyCode::Char(']') => self.queue_view.move_selected(false),
+                KeyCode::Char('c') if !self.queue_view.queue().is_empty() => {
+                    let prompt =
+                        Prompt::new("Очистити чергу?").hint("Enter — очистити, Esc — скасувати");
+                    self.open_prompt(prompt, PromptKind::ClearQueue);
+                }
+                _ => {}
+            },
+            3 => match code {
                 KeyCode::Up => self.player.change_volume(true),
                 KeyCode::Down => self.player.change_volume(false),
                 KeyCode::Right => self.next_song(),
                 KeyCode::Left => self.prev_song(),
                 KeyCode::Char('s') => self.player.stop(),
-                // KeyCode::Char(' ') => self.player.pause(),
                 _ => {}
-            }
-               
+            },
+            _ => {}
         }
-        _ => {}
     }
-}
 
-fn render_list(&self, frame: &mut Frame, area: Rect) {
+    fn open_seek_prompt(&mut self) {
+        let prompt = Prompt::new("Перейти до").hint("Наприклад 1:30 або 95, Esc — скасувати");
+        self.open_prompt(prompt, PromptKind::Seek);
+    }
+
+    fn set_mode(&self, mode: Mode) {
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.send(Action::SetMode(mode));
+        }
+    }
+
+    fn render_list(&self, frame: &mut Frame, area: Rect) {
         let mut state = ListState::default();
         state.select(Some(self.selected_index));
 
-        let list = List::new(self.list_items.clone())
-            .block(Block::default()
-                .title("Список")
-                .borders(Borders::ALL)
-                .border_style(self.border_style(0)))
+        let playlists = self
+            .playlists
+            .playlists()
+            .iter()
+            .map(|playlist| ListItem::new(format!("   ♫ {}", playlist.name)));
+        let items: Vec<ListItem> = self.list_items.iter().cloned().chain(playlists).collect();
+
+        let mut block = Block::default()
+            .title("Список")
+            .borders(Borders::ALL)
+            .border_style(self.border_style(0));
+        if self.selected_widget == 0 && self.selected_index >= PLAYLISTS {
+            let hint = match self.selected_playlist() {
+                Some(_) => " c новий · F2 назва · Del видалити · a в чергу · w експорт ",
+                None => " c новий · i імпорт ",
+            };
+            block =
+                block.title_bottom(Line::from(hint).style(Style::default().fg(Color::DarkGray)));
+        }
+
+        let list = List::new(items)
+            .block(block)
             .highlight_style(Style::default().bg(Color::Blue).fg(Color::White))
             .highlight_symbol("➤ ");
 
         frame.render_stateful_widget(list, area, &mut state);
-}
-
-
-fn render_song_list(&self, frame: &mut Frame, area: Rect) {
-    let area_width = area.width as usize;
-
-    let items: Vec<ListItem> = self.song_items.iter().map(|(title, ext, duration)| {
-        let song_duration = self.format_duration(duration);
-        let left = format!("{}.{:<4}", title, ext); // назва + розширення
-        let right = song_duration;
+    }
 
-        // Загальна довжина без пробілів
-        let total_len = left.len() + right.len();
-        let space = if area_width > total_len {
-            area_width - total_len - 4 // залишаємо трохи місця на "➤ " та рамки
-        } else {
-            1
-        };
+    /// The centered area of a popup report.
+    fn popup_area(area: Rect) -> Rect {
+        let [_, area, _] = Layout::horizontal([
+            Constraint::Percentage(10),
+            Constraint::Percentage(80),
+            Constraint::Percentage(10),
+        ])
+        .areas(area);
+        let [_, area, _] = Layout::vertical([
+            Constraint::Percentage(15),
+            Constraint::Percentage(70),
+            Constraint::Percentage(15),
+        ])
+        .areas(area);
+        area
+    }
 
-        let spacing = " ".repeat(space);
-        let line = Line::from(vec![
-            Span::raw(left),
-            Span::raw(spacing),
-            Span::styled(right, Style::default().fg(Color::Gray)),
-        ]);
+    fn render_scan_report(&self, frame: &mut Frame, area: Rect) {
+        let area = Self::popup_area(area);
+        let items: Vec<ListItem> = self
+            .scan_report
+            .errors
+            .iter()
+            .map(|error| {
+                ListItem::new(vec![
+                    Line::from(error.path.display().to_string()),
+                    Line::from(Span::styled(
+                        format!("  {}", error.reason),
+                        Style::default().fg(Color::Gray),
+                    )),
+                ])
+            })
+            .collect();
+
+        let list = List::new(items).block(
+            Block::default()
+                .title("Помилки сканування")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
 
-        ListItem::new(line)
-    }).collect();
+        frame.render_widget(Clear, area);
+        frame.render_widget(list, area);
+    }
 
-    let mut state = ListState::default();
-    state.select(Some(self.selected_song_index));
+    fn render_import_report(&self, frame: &mut Frame, area: Rect, report: &ImportReport) {
+        let area = Self::popup_area(area);
+        let items: Vec<ListItem> = report
+            .unresolved
+            .iter()
+            .map(|entry| {
+                let mut lines = vec![Line::from(entry.location.as_str())];
+                if let Some(title) = &entry.title {
+                    lines.push(Line::from(Span::styled(
+                        format!("  {title}"),
+                        Style::default().fg(Color::Gray),
+                    )));
+                }
+                ListItem::new(lines)
+            })
+            .collect();
 
-    let list = List::new(items)
-        .block(
+        let playlist = self.playlists.playlists().get(report.index);
+        let name = playlist.map_or("", |playlist| playlist.name.as_str());
+        let list = List::new(items).block(
             Block::default()
-                .title("Пісні")
+                .title(format!(
+                    "Не знайдено в «{name}»: {}",
+                    report.unresolved.len()
+                ))
+                .title_bottom(
+                    Line::from(" Esc — закрити ").style(Style::default().fg(Color::DarkGray)),
+                )
                 .borders(Borders::ALL)
-                .border_style(self.border_style(1)),
-        )
-        .highlight_style(Style::default().bg(Color::Blue).fg(Color::White))
-        .highlight_symbol("➤ ");
-
-    frame.render_stateful_widget(list, area, &mut state);
-}
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
 
+        frame.render_widget(Clear, area);
+        frame.render_widget(list, area);
+    }
 }
 
 impl Component for Home {
     fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.player.register_action_handler(tx.clone())?;
+        self.song_list.register_action_handler(tx.clone())?;
+        self.queue_view.register_action_handler(tx.clone())?;
         self.command_tx = Some(tx);
         Ok(())
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.player.register_config_handler(config.clone())?;
+        self.song_list.register_config_handler(config.clone())?;
+        self.queue_view.register_config_handler(config.clone())?;
         self.config = config;
         Ok(())
     }
 
+    fn init(&mut self, _area: Size) -> Result<()> {
+        self.load_favorites();
+        self.load_playlists();
+        self.load_history();
+        if self.resume {
+            self.restore_session();
+        }
+        self.start_scan();
+        Ok(())
+    }
+
     fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
         if let Some(tx) = &self.command_tx {
             let _ = tx.send(Action::Key(key));
@@ -301,24 +769,95 @@ impl Component for Home {
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
         self.player.update(action.clone())?;
 
-         if self.player.finished {
-          
-            self.next_song(); // або будь-яка твоя функція
+        // Перемикачі живуть у плеєрі (там і зберігаються), черга лише виконує
+        let queue = self.queue_view.queue_mut();
+        queue.set_shuffle(self.player.shuffle());
+        queue.set_repeat(self.player.repeat());
+
+        if self.player.continued {
+            // Плеєр уже грає наступний трек, черзі лишається наздогнати
+            self.player.continued = false;
+            self.queue_view.queue_mut().next_after_finished();
+        }
+        if self.player.finished {
+            self.player.finished = false;
+            if let Some(track) = self.queue_view.queue_mut().next_after_finished() {
+                self.player.play_sample(track);
+            }
         }
 
         match action {
-            Action::Key(key) => match key.code {
-                KeyCode::Tab if key.modifiers == KeyModifiers::NONE => self.next_widget(),
-                KeyCode::Up | KeyCode::Down|KeyCode::Right|KeyCode::Left|KeyCode::Char('s')|KeyCode::Char(' ')  => self.handle_list_navigation(key.code),
-                KeyCode::Enter => self.player.play_sample(  
-                    &self.song_items[self.selected_song_index].0,
-                    &self.song_items[self.selected_song_index].1,
-                    &self.song_items[self.selected_song_index].2,
-                ),
+            Action::ToggleScanReport => self.show_scan_report = !self.show_scan_report,
+            Action::ToggleStats => self.toggle_stats(),
+            Action::OpenSeekPrompt => self.open_seek_prompt(),
+            Action::SaveSession => self.save_session(),
+            Action::Enqueue => self.enqueue_selected(false),
+            Action::PlayNext => self.enqueue_selected(true),
+            Action::ToggleFavorite => self.toggle_favorite(),
+            Action::SetMode(mode) => self.mode = mode,
+            // Ключ, що відкрив підказку, приходить ще в режимі Home і до неї не потрапляє
+            Action::Key(key) if self.mode == Mode::Input => self.handle_prompt(key),
+            Action::AddToPlaylist => self.open_add_to_playlist_prompt(),
+            Action::RecordPlay(entry) => self.record_play(entry),
+            Action::LibraryTrackAdded(track) => {
+                self.library.insert((*track).clone());
+                self.song_list.upsert(*track);
+            }
+            Action::LibraryTrackRemoved(path) => {
+                self.library.remove_under(&path);
+                self.song_list.remove_under(&path);
+                self.queue_view.queue_mut().remove_under(&path);
+                self.queue_view.clamp_cursor();
+                self.refresh_view();
+            }
+            Action::LibraryScanProgress(scanned, total) => {
+                self.song_list.set_scan_progress(Some((scanned, total)))
+            }
+            Action::LibraryScanDone(report) => {
+                info!(
+                    "Library scan finished: {} tracks, {} unreadable files",
+                    self.library.tracks().len(),
+                    report.errors.len()
+                );
+                self.song_list.set_scan_progress(None);
+                self.song_list.set_scan_errors(report.errors.len());
+                self.scan_report = report;
+                // Треки списків тепер можна знайти в бібліотеці
+                self.refresh_view();
+            }
+            // Клавіші з модифікаторами (Shift/Ctrl + стрілки) — це перемотування
+            Action::Key(key) if key.modifiers == KeyModifiers::NONE => match key.code {
+                KeyCode::Esc if self.stats_view.is_some() => self.stats_view = None,
+                // Поки відкрита статистика, панелі під нею не реагують на клавіші
+                _ if self.stats_view.is_some() => {}
+                KeyCode::Tab => self.next_widget(),
+                KeyCode::Up
+                | KeyCode::Down
+                | KeyCode::Right
+                | KeyCode::Left
+                | KeyCode::Char('s')
+                | KeyCode::Char('x')
+                | KeyCode::Char('[')
+                | KeyCode::Char(']')
+                | KeyCode::Char('c')
+                | KeyCode::Char('i')
+                | KeyCode::Char('w')
+                | KeyCode::F(2)
+                | KeyCode::Delete => self.handle_list_navigation(key.code),
+                KeyCode::Esc => self.import_report = None,
+                KeyCode::Enter if self.selected_widget == 2 => self.play_queued(),
+                KeyCode::Enter if self.selected_widget == 0 => match self.selected_playlist() {
+                    Some(index) => self.play_playlist(index),
+                    None => self.play_selected(),
+                },
+                KeyCode::Enter => self.play_selected(),
                 _ => {}
             },
             _ => {}
         }
+        // Наступний трек декодується заздалегідь, щоб зіграти його без паузи. Після будь-якої
+        // зміни черги тут же перевіряємо, чи це досі той самий трек.
+        self.player.preload(self.queue_view.queue().upcoming());
         Ok(None)
     }
 
@@ -333,10 +872,27 @@ impl Component for Home {
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
             .split(chunks[1]);
 
+        let top_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(right_chunks[0]);
+
         self.render_list(frame, chunks[0]);
-        self.render_song_list(frame, right_chunks[0]);
-        // self.render_player(frame, right_chunks[1]);
+        self.song_list.draw(frame, top_chunks[0])?;
+        self.queue_view.draw(frame, top_chunks[1])?;
         self.player.draw(frame, right_chunks[1])?;
+        if self.show_scan_report {
+            self.render_scan_report(frame, area);
+        }
+        if let Some(report) = &self.import_report {
+            self.render_import_report(frame, area, report);
+        }
+        if let Some(stats_view) = &self.stats_view {
+            stats_view.draw(frame, area);
+        }
+        if let Some((prompt, _)) = &self.prompt {
+            prompt.draw(frame, area);
+        }
 
         Ok(())
     }
@@ -1,97 +1,220 @@
-use std::fs::File;
-use std::io::{BufReader, Cursor};
-use std::sync::{Arc, Mutex};
-
 use color_eyre::Result;
-use crossterm::event::KeyCode;
 use ratatui::{prelude::*, widgets::*};
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
-
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
 
 use super::Component;
-use crate::{action::Action, config::Config};
-use std::time::{Duration, Instant};
+use crate::{
+    action::Action,
+    audio::{AudioEngine, Command, PlaybackEvent},
+    config::{Config, VolumeCurve},
+    history::{HistoryEntry, PlayEvent},
+    library::Track,
+    queue::Repeat,
+    storage,
+};
+use std::{path::PathBuf, time::Duration};
+
+/// Player settings that survive a restart, kept in the data directory.
+const STATE_FILE: &str = "player.json";
+
+/// The engine reports the position about ten times a second; a bigger jump is a seek, and the
+/// skipped part was not heard.
+const MAX_POSITION_STEP: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct PlayerState {
+    volume: f32,
+    muted: bool,
+    shuffle: bool,
+    repeat: Repeat,
+    crossfade: bool,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            volume: 0.5,
+            muted: false,
+            shuffle: false,
+            repeat: Repeat::Off,
+            crossfade: false,
+        }
+    }
+}
+
+/// Output amplitude for a volume `level` between 0.0 and 1.0.
+fn amplitude(level: f32, curve: VolumeCurve) -> f32 {
+    match curve {
+        VolumeCurve::Linear => level,
+        // -60 dB at the lowest step, 0 dB at full volume
+        VolumeCurve::Logarithmic if level > 0.0 => 10f32.powf(3.0 * (level - 1.0)),
+        VolumeCurve::Logarithmic => 0.0,
+    }
+}
+
+/// Parses a timestamp typed by the user: `90`, `1:30`, `1:02:03`, with optional fractions of a
+/// second (`1:30.5`).
+pub fn parse_timestamp(raw: &str) -> Option<Duration> {
+    let mut parts = raw.trim().rsplit(':');
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    let mut total = seconds;
+    for unit in [60.0, 3600.0] {
+        let Some(part) = parts.next() else {
+            break;
+        };
+        total += part.parse::<u32>().ok()? as f64 * unit;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+/// How much of the current track was heard so far, for the play history.
+#[derive(Debug)]
+struct Listening {
+    track: Track,
+    /// The start is recorded once the track is actually heard, so a track cued on launch and
+    /// never resumed leaves no trace.
+    started: bool,
+    listened: Duration,
+    /// Last reported position.
+    position: Duration,
+}
+
 #[derive(Default)]
 pub struct Player {
-   command_tx: Option<UnboundedSender<Action>>,
+    command_tx: Option<UnboundedSender<Action>>,
     config: Config,
-    current_track: Option<(String, String)>, // Назва, автор
+    current_track: Option<Track>,
     volume: f32,
+    muted: bool,
+    shuffle: bool,
+    repeat: Repeat,
+    crossfade: bool,
     position: Duration,
     duration: Duration,
-    playback_start_time: Option<Instant>,
-    pub finished: bool,
+    playing: bool,
     paused: bool,
-    sink: Option<Arc<Mutex<Sink>>>,
-    _stream: Option<OutputStream>,
-    stream_handle: Option<OutputStreamHandle>,
+    pub finished: bool,
+    /// Set when the preloaded track took over; the queue has to move on without loading it.
+    pub continued: bool,
+    /// Preloaded behind the current track and how long it fades in, see [`Player::preload`].
+    next_track: Option<Track>,
+    next_crossfade: Duration,
+    /// A track was loaded but has not started yet, so events from the previous one are stale.
+    loading: bool,
+    listening: Option<Listening>,
+    /// Last playback error, shown in the pane until the next track starts.
+    error: Option<String>,
+    /// Started once the action sender is registered; plays on its own thread.
+    engine: Option<AudioEngine>,
 }
 
 impl Player {
     pub fn new() -> Self {
-        
-        let (_stream, stream_handle) = match OutputStream::try_default() {
-            Ok((s, h)) => (Some(s), Some(h)),
-            Err(_) => (None, None),
-        };
-
-        let sink = stream_handle
-            .as_ref()
-            .map(|handle| Sink::try_new(handle).ok())
-            .flatten()
-            .map(|s| Arc::new(Mutex::new(s)));
-
         Self {
-            command_tx: None,
-            config: Config::default(),
-            current_track: Some(("Невідомий трек".to_string(), "Невідомий автор".to_string())),
             volume: 0.5,
-            position: Duration::from_secs(0),
-            duration: Duration::from_secs(0),
-            playback_start_time: None,
-            sink,
-            _stream,
-            stream_handle,
-             ..Default::default()
+            ..Default::default()
         }
     }
-        fn render_player(&self, frame: &mut Frame, area: Rect) {
-        let (title, artist) = self.current_track.clone().unwrap_or_else(|| ("Невідомо".into(), "Невідомо".into()));
+
+    fn render_player(&self, frame: &mut Frame, area: Rect) {
+        let (title, artist, album) = match &self.current_track {
+            Some(track) => (
+                track.title(),
+                track.artist().unwrap_or("Невідомий автор").to_string(),
+                track.album().unwrap_or_default().to_string(),
+            ),
+            None => ("Невідомо".into(), "Невідомо".into(), String::new()),
+        };
         let position_secs = self.position.as_secs();
         let duration_secs = self.duration.as_secs();
         let remaining_secs = duration_secs.saturating_sub(position_secs);
 
-
         let format_time = |s: u64| format!("{}:{:02}", s / 60, s % 60);
 
+        let status = match (self.paused, self.playing) {
+            (true, _) => Span::styled(
+                "⏸ Paused (",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            (false, true) => Span::raw("Playing ("),
+            (false, false) => Span::raw("Stopped ("),
+        };
+
+        let volume = if self.muted {
+            " | Volume: muted )".to_string()
+        } else {
+            format!(" | Volume: {}% )", self.volume_percent())
+        };
+
+        let shuffle = if self.shuffle { "On" } else { "Off" };
+        let crossfade = match self.crossfade_length() {
+            length if length.is_zero() => " | Crossfade: Off ".to_string(),
+            length => format!(" | Crossfade: {}s ", length.as_secs_f32()),
+        };
+
         let text = Text::from(vec![
             Line::from(vec![
-                Span::raw("Playing ("),
-                Span::raw(" Shuffle: On "),
-                Span::raw(" | Repeat: Off "),
-                Span::raw(" | Volume: 98% )"),
+                status,
+                Span::raw(format!(" Shuffle: {shuffle} ")),
+                Span::raw(format!(" | Repeat: {} ", self.repeat)),
+                Span::raw(crossfade),
+                Span::raw(volume),
             ]),
             Line::from(""),
+            Line::from(vec![Span::styled(
+                &title,
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(artist),
+            Line::from(Span::styled(album, Style::default().fg(Color::DarkGray))),
+            match &self.error {
+                Some(error) => Line::from(Span::styled(
+                    format!("⚠ {error}"),
+                    Style::default().fg(Color::Red),
+                )),
+                None => Line::from(""),
+            },
             Line::from(vec![
-                Span::styled(&title, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-            ]),
-            Line::from(""),
-            // Line::from(artist),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled(format_time(position_secs), Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC)),
-                Span::styled(format!("/{}", format_time(duration_secs)), Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC)),
-                Span::styled(format!(" (-{})", format_time(remaining_secs)), Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC)),
+                Span::styled(
+                    format_time(position_secs),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::ITALIC),
+                ),
+                Span::styled(
+                    format!("/{}", format_time(duration_secs)),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::ITALIC),
+                ),
+                Span::styled(
+                    format!(" (-{})", format_time(remaining_secs)),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::ITALIC),
+                ),
             ]),
         ]);
 
-        let paragraph = Paragraph::new(text)
-            .alignment(Alignment::Center)
-            .block(Block::default()
+        let paragraph = Paragraph::new(text).alignment(Alignment::Center).block(
+            Block::default()
                 .title("Плеєр")
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::LightBlue)));
+                .border_style(Style::default().fg(Color::LightBlue)),
+        );
 
         frame.render_widget(paragraph, area);
 
@@ -102,107 +225,356 @@ impl Player {
             height: 1,
         };
 
-    let progress = if self.duration > Duration::ZERO {
-    self.position.as_secs_f64() / self.duration.as_secs_f64()
-} else {
-    0.0
-};
+        // Тривалість зі сканера може бути трохи меншою за справжню
+        let progress = if self.duration > Duration::ZERO {
+            (self.position.as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+        } else {
+            0.0
+        };
 
-let gauge = Gauge::default()
-    .gauge_style(Style::default().fg(Color::Yellow))
-    .ratio(progress);
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(Color::Yellow))
+            .ratio(progress);
 
         frame.render_widget(gauge, gauge_area);
-    }
 
-    pub fn change_volume(&mut self, action: bool) {
-        if action {
-            if self.volume < 1.0 {
-                self.volume += 0.1
+        if area.height > 2 {
+            let volume_area = Rect {
+                x: area.x + 2,
+                y: area.y + area.height - 2,
+                width: area.width.saturating_sub(4).min(40),
+                height: 1,
+            };
+            let (label, color) = if self.muted {
+                ("🔇 Вимкнено".to_string(), Color::DarkGray)
             } else {
-               if self.volume >= 0.1 {
-                 self.volume -= 0.1
-               }
-            }
+                (
+                    format!("🔊 {:>3}%", self.volume_percent()),
+                    Color::LightBlue,
+                )
+            };
+            let volume_gauge = LineGauge::default()
+                .label(label)
+                .filled_style(Style::default().fg(color))
+                .ratio(f64::from(self.volume));
+            frame.render_widget(volume_gauge, volume_area);
+        }
+    }
+
+    fn volume_percent(&self) -> u32 {
+        (self.volume * 100.0).round() as u32
+    }
+
+    /// Moves the volume one configured step up or down, unmuting the player.
+    pub fn change_volume(&mut self, up: bool) {
+        let step = self.config.player.volume_step;
+        let volume = if up {
+            self.volume + step
+        } else {
+            self.volume - step
+        };
+        // Округлюємо, щоб кроки по 0.05 не накопичували похибку
+        self.volume = ((volume * 1000.0).round() / 1000.0).clamp(0.0, 1.0);
+        self.muted = false;
+        self.apply_volume();
+        self.save_state();
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.apply_volume();
+        self.save_state();
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    pub fn toggle_shuffle(&mut self) {
+        self.shuffle = !self.shuffle;
+        self.save_state();
+    }
+
+    pub fn cycle_repeat(&mut self) {
+        self.repeat = self.repeat.cycle();
+        self.save_state();
+    }
+
+    pub fn toggle_crossfade(&mut self) {
+        self.crossfade = !self.crossfade;
+        self.save_state();
+    }
+
+    fn crossfade_length(&self) -> Duration {
+        if self.crossfade {
+            self.config.player.crossfade()
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn apply_volume(&self) {
+        self.send(Command::SetVolume(self.output_volume()));
+    }
+
+    fn send(&self, command: Command) {
+        if let Some(engine) = &self.engine {
+            engine.send(command);
+        }
+    }
+
+    fn output_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            amplitude(self.volume, self.config.player.volume_curve)
+        }
+    }
+
+    fn state_path(&self) -> std::path::PathBuf {
+        self.config.config.data_dir.join(STATE_FILE)
+    }
+
+    fn load_state(&mut self) {
+        let state = storage::load_json::<PlayerState>(&self.state_path()).unwrap_or_else(|err| {
+            warn!("Ignoring saved player state: {err}");
+            None
+        });
+        let state = state.unwrap_or_default();
+        self.volume = state.volume.clamp(0.0, 1.0);
+        self.muted = state.muted;
+        self.shuffle = state.shuffle;
+        self.repeat = state.repeat;
+        self.crossfade = state.crossfade;
+        self.apply_volume();
+    }
+
+    fn save_state(&self) {
+        let state = PlayerState {
+            volume: self.volume,
+            muted: self.muted,
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+            crossfade: self.crossfade,
+        };
+        if let Err(err) = storage::save_json(&self.state_path(), &state) {
+            warn!("Failed to save player state: {err}");
         }
-      
     }
 
     pub fn stop(&mut self) {
-        if let Some(sink) = &self.sink {
-            sink.lock().unwrap().stop();
-            self.position = Duration::from_secs(0);
-            self.playback_start_time = None;
-        }
-    }
-
-    // pub fn pause(&mut self) {
-    //     if let Some(sink) = &self.sink {
-    //         if self.paused {
-    //             // Якщо вже на паузі, відновлюємо
-    //             sink.lock().unwrap().play();
-    //             self.playback_start_time = Some(Instant::now());
-    //             self.paused = false;
-    //         } else {
-    //             // Якщо не на паузі, ставимо на паузуs
-    //             self.paused = true;
-    //              sink.lock().unwrap().pause();
-    //              if let Some(start) = self.playback_start_time {
-    //                     self.position += Instant::now().saturating_duration_since(start);
-    //                     self.playback_start_time = None;
-    //                 }
-    //         }
-           
-    //     }
-    // }
-
-    pub fn play_sample(&mut self, name: &str, ext: &str, duration: &u64) {
-        // Створюємо шлях до файлу
-        let path = format!("local_music/{}.{}", name, ext);
-
-        // Перевіряємо чи є вже Sink — якщо є, зупиняємо
-           if let Some(sink) = &self.sink {
-        // Зупиняємо поточний трек
-        sink.lock().unwrap().stop();
-
-        // Формуємо шлях до файлу
-        let path = format!("./local_music/{}.{}", name, ext);
-        if let Ok(file) = File::open(&path) {
-            let source = Decoder::new(BufReader::new(file));
-            if let Ok(source) = source {
-                sink.lock().unwrap().append(source);
-
-                // Оновлюємо внутрішній стан
-                self.current_track = Some((name.to_string(), "Невідомий автор".to_string())); // заміни "Невідомий автор", якщо можеш витягти
-                self.position = Duration::from_secs(0);
-                self.duration = Duration::from_secs(*duration);
-                self.playback_start_time = Some(Instant::now());
-            }
+        self.send(Command::Stop);
+        self.next_track = None;
+        self.end_listening(PlayEvent::Skip);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match (self.playing, self.paused) {
+            (true, true) => self.send(Command::Play),
+            (true, false) => self.send(Command::Pause),
+            // Нічого не грає — нема чого ставити на паузу
+            (false, _) => {}
+        }
+    }
+
+    /// Jumps to `to` in the current track, clamped to its length.
+    pub fn seek(&mut self, to: Duration) {
+        if !self.playing {
+            return;
+        }
+        let to = if self.duration.is_zero() {
+            to
+        } else {
+            to.min(self.duration)
+        };
+        self.send(Command::Seek(to));
+        // Показуємо нову позицію одразу, не чекаючи відповіді рушія
+        self.position = to;
+    }
+
+    /// Moves `seconds` forward, or backward if negative.
+    pub fn seek_by(&mut self, seconds: i64) {
+        let offset = Duration::from_secs(seconds.unsigned_abs());
+        let to = if seconds < 0 {
+            self.position.saturating_sub(offset)
         } else {
-            eprintln!("Не вдалося відкрити файл: {}", path);
-        }
-    }
-
-        // Створюємо новий Sink
-        if let Some(handle) = &self.stream_handle {
-            if let Ok(new_sink) = Sink::try_new(handle) {
-                if let Ok(file) = File::open(&path) {
-                    let reader = BufReader::new(file);
-                    if let Ok(source) = Decoder::new(reader) {
-                        new_sink.append(source);
-                        self.sink = Some(Arc::new(Mutex::new(new_sink)));
-                        
-                    } else {
-                        eprintln!("❌ Не вдалося декодувати: {}", path);
-                    }
-                } else {
-                    eprintln!("❌ Не вдалося відкрити: {}", path);
+            self.position + offset
+        };
+        self.seek(to);
+    }
+
+    pub fn seek_to_percent(&mut self, percent: u8) {
+        self.seek(self.duration.mul_f64(f64::from(percent.min(100)) / 100.0));
+    }
+
+    pub fn play_sample(&mut self, track: &Track) {
+        self.start(track, Command::Load(track.path.clone()), Duration::ZERO);
+    }
+
+    /// Loads `track` paused at `position`, ready to go on where it was left.
+    pub fn cue(&mut self, track: &Track, position: Duration) {
+        let path = track.path.clone();
+        self.start(track, Command::Cue { path, position }, position);
+    }
+
+    pub fn current_track(&self) -> Option<&Track> {
+        self.current_track.as_ref()
+    }
+
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    fn start(&mut self, track: &Track, command: Command, position: Duration) {
+        self.send(command);
+        self.current_track = Some(track.clone());
+        self.position = position;
+        self.duration = track.duration;
+        self.finished = false;
+        self.continued = false;
+        // Loading drops the preload in the engine as well
+        self.next_track = None;
+        self.loading = true;
+        self.begin_listening(track, position);
+    }
+
+    /// Starts counting how long `track` is heard; whatever was heard before ends as skipped.
+    fn begin_listening(&mut self, track: &Track, position: Duration) {
+        self.end_listening(PlayEvent::Skip);
+        self.listening = Some(Listening {
+            track: track.clone(),
+            started: false,
+            listened: Duration::ZERO,
+            position,
+        });
+    }
+
+    fn end_listening(&mut self, event: PlayEvent) {
+        if let Some(listening) = self.listening.take()
+            && listening.started
+        {
+            self.record(HistoryEntry::new(
+                event,
+                &listening.track,
+                listening.listened,
+            ));
+        }
+    }
+
+    /// Counts playback up to `position` as heard, unless it jumped there or the track is
+    /// paused: a track cued on launch reports its start and then the saved position.
+    fn listen_until(&mut self, position: Duration) {
+        let paused = self.paused;
+        let Some(listening) = &mut self.listening else {
+            return;
+        };
+        let heard = position
+            .checked_sub(listening.position)
+            .filter(|heard| !paused && !heard.is_zero() && *heard <= MAX_POSITION_STEP);
+        listening.position = position;
+        let Some(heard) = heard else {
+            return;
+        };
+        listening.listened += heard;
+        if !listening.started {
+            listening.started = true;
+            let entry = HistoryEntry::new(PlayEvent::Start, &listening.track, Duration::ZERO);
+            self.record(entry);
+        }
+    }
+
+    fn record(&self, entry: HistoryEntry) {
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.send(Action::RecordPlay(entry));
+        }
+    }
+
+    /// Has the engine decode `track` ahead of time and play it right after the current one,
+    /// crossfading unless both come from the same album. Cheap to call repeatedly: the engine
+    /// only hears about changes.
+    pub fn preload(&mut self, track: Option<&Track>) {
+        // Як і в статистиці, альбом — разом із виконавцем: "Greatest Hits" буває в багатьох
+        let same_album = |track: &Track| {
+            self.current_track.as_ref().is_some_and(|current| {
+                track.album().is_some()
+                    && current.album() == track.album()
+                    && current.artist() == track.artist()
+            })
+        };
+        let crossfade = match track {
+            // Альбоми часто зведені без пауз між треками, там накладання лише заважає
+            Some(track) if same_album(track) => Duration::ZERO,
+            _ => self.crossfade_length(),
+        };
+        let path = |track: Option<&Track>| track.map(|track| track.path.clone());
+        if path(track) == path(self.next_track.as_ref()) && crossfade == self.next_crossfade {
+            return;
+        }
+        self.send(Command::Preload {
+            path: path(track),
+            crossfade,
+        });
+        self.next_track = track.cloned();
+        self.next_crossfade = crossfade;
+    }
+
+    fn handle_playback(&mut self, event: PlaybackEvent) {
+        let is_current =
+            |path: &PathBuf| self.current_track.as_ref().is_some_and(|t| &t.path == path);
+        match event {
+            PlaybackEvent::Started(path) if is_current(&path) => {
+                self.loading = false;
+                self.playing = true;
+                self.paused = false;
+                self.error = None;
+            }
+            PlaybackEvent::Paused => self.paused = true,
+            PlaybackEvent::Resumed => self.paused = false,
+            PlaybackEvent::Stopped => {
+                self.playing = false;
+                self.paused = false;
+                self.position = Duration::ZERO;
+            }
+            // Поки новий трек вантажиться, позиції приходять ще від попереднього
+            PlaybackEvent::Position(position) if self.playing && !self.loading => {
+                self.position = position;
+                self.listen_until(position);
+            }
+            // Подія від попереднього треку, якщо користувач уже ввімкнув інший, не рахується
+            PlaybackEvent::Finished(path) if is_current(&path) => {
+                self.playing = false;
+                self.paused = false;
+                self.finished = true; // <-- сигнал
+                self.end_listening(PlayEvent::Complete);
+            }
+            PlaybackEvent::Continued(_) if self.loading => {}
+            PlaybackEvent::Continued(path) => match self.next_track.take() {
+                Some(track) if track.path == path => {
+                    self.end_listening(PlayEvent::Complete);
+                    self.begin_listening(&track, Duration::ZERO);
+                    self.position = Duration::ZERO;
+                    self.duration = track.duration;
+                    self.current_track = Some(track);
+                    self.continued = true;
                 }
-            } else {
-                eprintln!("❌ Не вдалося створити Sink");
+                // Черга змінилась саме тоді, коли трек закінчився: вмикаємо те, що в ній тепер
+                _ => {
+                    self.end_listening(PlayEvent::Complete);
+                    self.finished = true;
+                }
+            },
+            PlaybackEvent::Error(message) => {
+                self.loading = false;
+                warn!("{message}");
+                self.playing = false;
+                self.error = Some(message);
+                self.end_listening(PlayEvent::Skip);
             }
-        } else {
-            eprintln!("❌ Відсутній stream_handle");
+            _ => {}
         }
     }
 }
@@ -214,22 +586,29 @@ impl Component for Player {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        if let Some(tx) = &self.command_tx {
+            self.engine = Some(AudioEngine::spawn(config.player.output.clone(), tx.clone()));
+        }
         self.config = config;
+        self.load_state();
         Ok(())
     }
 
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
         match action {
-            Action::Tick => {
-               if let Some(start) = self.playback_start_time {
-                let now = Instant::now();
-                let elapsed = now.saturating_duration_since(start);
-                self.position = elapsed.min(self.duration);
-               
-}              if self.position >= self.duration && self.duration > Duration::ZERO {
-                self.finished = true; // <-- сигнал
-                }
-            }
+            Action::Playback(event) => self.handle_playback(event),
+            Action::TogglePause => self.toggle_pause(),
+            Action::SeekBy(seconds) => self.seek_by(seconds),
+            Action::SeekToPercent(percent) => self.seek_to_percent(percent),
+            Action::SeekTo(to) => self.seek(to),
+            Action::VolumeUp => self.change_volume(true),
+            Action::VolumeDown => self.change_volume(false),
+            Action::ToggleMute => self.toggle_mute(),
+            Action::ToggleShuffle => self.toggle_shuffle(),
+            Action::CycleRepeat => self.cycle_repeat(),
+            Action::ToggleCrossfade => self.toggle_crossfade(),
+            // Що встигли послухати до виходу, теж рахується
+            Action::Quit => self.end_listening(PlayEvent::Skip),
             Action::Render => {
                 // наприклад, запускати звук якщо потрібно
             }
@@ -239,7 +618,137 @@ impl Component for Player {
     }
 
     fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
-    self.render_player(frame, area);
+        self.render_player(frame, area);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+    use super::*;
+
+    #[test]
+    fn test_volume_curves() {
+        assert_eq!(amplitude(0.5, VolumeCurve::Linear), 0.5);
+        assert_eq!(amplitude(1.0, VolumeCurve::Logarithmic), 1.0);
+        assert_eq!(amplitude(0.0, VolumeCurve::Logarithmic), 0.0);
+        // halfway is -30 dB, far quieter than half the amplitude
+        let half = amplitude(0.5, VolumeCurve::Logarithmic);
+        assert!((half - 0.031_622_78).abs() < 1e-6);
+    }
+
+    /// Feeds `events` to `player` and returns the history entries it sent.
+    fn play(
+        player: &mut Player,
+        rx: &mut UnboundedReceiver<Action>,
+        events: impl IntoIterator<Item = Action>,
+    ) -> Vec<(PlayEvent, Duration)> {
+        for event in events {
+            player.update(event).unwrap();
+        }
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|action| match action {
+                Action::RecordPlay(entry) => Some((entry.event, entry.listened)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cued_track_is_recorded_only_once_resumed() {
+        let (tx, mut rx) = unbounded_channel();
+        let mut player = Player::new();
+        player.register_action_handler(tx).unwrap();
+        let track = Track::new(PathBuf::from("/a.mp3"));
+        let at = |millis| Action::Playback(PlaybackEvent::Position(Duration::from_millis(millis)));
+
+        // What the engine reports for a cue: the start of the track, then the saved position
+        player.cue(&track, Duration::from_millis(500));
+        let events = [
+            Action::Playback(PlaybackEvent::Started(track.path.clone())),
+            at(0),
+            Action::Playback(PlaybackEvent::Paused),
+            at(500),
+        ];
+        assert_eq!(play(&mut player, &mut rx, events), []);
+        assert_eq!(
+            play(&mut player, &mut rx, [at(600), Action::Quit]),
+            [],
+            "positions while paused are not listening"
+        );
+
+        player.cue(&track, Duration::from_millis(500));
+        let events = [
+            Action::Playback(PlaybackEvent::Started(track.path.clone())),
+            at(0),
+            Action::Playback(PlaybackEvent::Paused),
+            at(500),
+            Action::Playback(PlaybackEvent::Resumed),
+            at(700),
+            Action::Quit,
+        ];
+        assert_eq!(
+            play(&mut player, &mut rx, events),
+            [
+                (PlayEvent::Start, Duration::ZERO),
+                (PlayEvent::Skip, Duration::from_millis(200))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_positions_of_the_previous_track_are_ignored_while_loading() {
+        let mut player = Player::new();
+        let (first, second) = (
+            Track::new(PathBuf::from("/a.mp3")),
+            Track::new(PathBuf::from("/b.mp3")),
+        );
+        let started = |track: &Track| Action::Playback(PlaybackEvent::Started(track.path.clone()));
+        let at = |secs| Action::Playback(PlaybackEvent::Position(Duration::from_secs(secs)));
+
+        player.play_sample(&first);
+        player.update(started(&first)).unwrap();
+        player.update(at(40)).unwrap();
+        assert_eq!(player.position(), Duration::from_secs(40));
+
+        player.play_sample(&second);
+        player.update(at(41)).unwrap();
+        assert_eq!(player.position(), Duration::ZERO);
+        player.update(started(&second)).unwrap();
+        player.update(at(1)).unwrap();
+        assert_eq!(player.position(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_crossfade_skips_only_the_same_album_by_the_same_artist() {
+        let track = |path: &str, artist: &str| {
+            let mut track = Track::new(PathBuf::from(path));
+            track.tags.artist = Some(artist.to_string());
+            track.tags.album = Some("Greatest Hits".to_string());
+            track
+        };
+        let mut player = Player::new();
+        player.crossfade = true;
+        player.current_track = Some(track("/a.mp3", "ABBA"));
+
+        player.preload(Some(&track("/b.mp3", "ABBA")));
+        assert_eq!(player.next_crossfade, Duration::ZERO);
+        player.preload(Some(&track("/c.mp3", "Queen")));
+        assert_eq!(player.next_crossfade, player.config.player.crossfade());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp(" 1:30 "), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_timestamp("0:01.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("-5"), None);
+        assert_eq!(parse_timestamp("abc"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+}
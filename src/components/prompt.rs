@@ -0,0 +1,102 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+
+/// What a key press did to a [`Prompt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptEvent {
+    Pending,
+    Submit(String),
+    Cancel,
+}
+
+/// A one-line text input shown as a popup over the other panes.
+///
+/// While a prompt is open the app should be in [`Mode::Input`](crate::app::Mode::Input), so
+/// letters typed into it do not trigger keybindings.
+#[derive(Debug, Default)]
+pub struct Prompt {
+    title: String,
+    hint: String,
+    input: String,
+    /// Why the last submitted input was not accepted, shown instead of the hint.
+    error: Option<String>,
+}
+
+impl Prompt {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Grey text under the input explaining what to type.
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = hint.into();
+        self
+    }
+
+    /// Text the input starts with, e.g. the current name when renaming.
+    pub fn input(mut self, input: impl Into<String>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Keeps the prompt open and tells the user what is wrong with the input.
+    pub fn set_error(&mut self, error: impl Into<String>) {
+        self.error = Some(error.into());
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptEvent {
+        self.error = None;
+        match key.code {
+            KeyCode::Enter => PromptEvent::Submit(self.input.trim().to_string()),
+            KeyCode::Esc => PromptEvent::Cancel,
+            KeyCode::Backspace => {
+                self.input.pop();
+                PromptEvent::Pending
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.push(c);
+                PromptEvent::Pending
+            }
+            _ => PromptEvent::Pending,
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(4).min(50);
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + area.height.saturating_sub(4) / 2,
+            width,
+            height: 4.min(area.height),
+        };
+
+        let text = Text::from(vec![
+            Line::from(vec![
+                Span::raw(self.input.as_str()),
+                Span::styled("█", Style::default().fg(Color::Gray)),
+            ]),
+            match &self.error {
+                Some(error) => Line::from(Span::styled(
+                    error.as_str(),
+                    Style::default().fg(Color::Red),
+                )),
+                None => Line::from(Span::styled(
+                    self.hint.as_str(),
+                    Style::default().fg(Color::DarkGray),
+                )),
+            },
+        ]);
+        let paragraph = Paragraph::new(text).block(
+            Block::default()
+                .title(self.title.as_str())
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+}
@@ -0,0 +1,140 @@
+use color_eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::Component;
+use crate::{action::Action, config::Config, library::Track, queue::Queue};
+
+/// The "Черга" pane: the play queue with its own cursor, separate from the song list.
+#[derive(Default)]
+pub struct QueueView {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    queue: Queue,
+    cursor: usize,
+    focused: bool,
+}
+
+impl QueueView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+
+    pub fn queue_mut(&mut self) -> &mut Queue {
+        &mut self.queue
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn selected_track(&self) -> Option<&Track> {
+        self.queue.tracks().get(self.cursor)
+    }
+
+    pub fn select_previous(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.cursor + 1 < self.queue.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Removes the track under the cursor.
+    pub fn remove_selected(&mut self) {
+        self.queue.remove(self.cursor);
+        self.clamp_cursor();
+    }
+
+    /// Moves the track under the cursor up or down, and the cursor with it.
+    pub fn move_selected(&mut self, up: bool) {
+        if let Some(index) = self.queue.move_track(self.cursor, up) {
+            self.cursor = index;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.cursor = 0;
+    }
+
+    /// Keeps the cursor on a track after the queue shrank.
+    pub fn clamp_cursor(&mut self) {
+        self.cursor = self.cursor.min(self.queue.len().saturating_sub(1));
+    }
+
+    fn border_style(&self) -> Style {
+        if self.focused {
+            Style::default().fg(Color::White)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        }
+    }
+}
+
+impl Component for QueueView {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        let current = self.queue.current_index();
+        let items: Vec<ListItem> = self
+            .queue
+            .tracks()
+            .iter()
+            .enumerate()
+            .map(|(index, track)| {
+                if Some(index) == current {
+                    ListItem::new(format!("▶ {}", track.display_name())).style(
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    ListItem::new(format!("  {}", track.display_name()))
+                }
+            })
+            .collect();
+
+        let mut state = ListState::default();
+        if self.focused && !self.queue.is_empty() {
+            state.select(Some(self.cursor));
+        }
+
+        let mut block = Block::default()
+            .title(format!("Черга ({})", self.queue.len()))
+            .borders(Borders::ALL)
+            .border_style(self.border_style());
+        if self.focused {
+            block = block.title_bottom(
+                Line::from(" Enter грати · x прибрати · [ ] порядок · c очистити ")
+                    .style(Style::default().fg(Color::DarkGray)),
+            );
+        }
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().bg(Color::Blue).fg(Color::White))
+            .highlight_symbol("➤ ");
+
+        frame.render_stateful_widget(list, area, &mut state);
+        Ok(())
+    }
+}
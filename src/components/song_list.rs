@@ -1,32 +1,153 @@
+use std::{path::Path, time::Duration};
+
 use color_eyre::Result;
 use ratatui::{prelude::*, widgets::*};
 
-
 use tokio::sync::mpsc::UnboundedSender;
 
 use super::Component;
-use crate::{action::Action, config::Config};
+use crate::{
+    action::Action,
+    config::Config,
+    history::trends::Trend,
+    library::{Track, favorites::Favorites},
+};
+
+/// What the song list shows.
+#[derive(Debug, Clone, Default)]
+pub enum View {
+    /// The whole library.
+    #[default]
+    All,
+    /// Only the liked tracks of the library.
+    Favorites,
+    /// A playlist's tracks, in playlist order.
+    Playlist { name: String, tracks: Vec<Track> },
+    /// One of the "Тренди" lists, ranked.
+    Trend { trend: Trend, tracks: Vec<Track> },
+}
 
+/// The "Пісні" pane: a scrollable list of tracks with a selection cursor, showing the library,
+/// the liked tracks, a playlist or a trend.
 #[derive(Default)]
 pub struct SongList {
     command_tx: Option<UnboundedSender<Action>>,
     config: Config,
     selected_song_index: usize,
-    song_items: Vec<(String, String)>,
-    border_style: Style,   
+    song_items: Vec<Track>,
+    focused: bool,
+    scan_errors: usize,
+    scan_progress: Option<(usize, usize)>,
+    favorites: Favorites,
+    view: View,
 }
 
 impl SongList {
-    pub fn default() -> Self {
-        Self {
-            command_tx: None,
-            config: Config::default(),
-            selected_song_index: 0,
-            song_items: vec![
-                ("Super long string ".into(), "3:32".into());
-                4
-            ],
-            border_style: Style::default().fg(Color::DarkGray),
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a track, or updates it in place if it is already listed.
+    pub fn upsert(&mut self, track: Track) {
+        match self.song_items.iter_mut().find(|item| item.id == track.id) {
+            Some(item) => *item = track,
+            None => self.song_items.push(track),
+        }
+    }
+
+    /// Drops the track at `path`, or every track inside it if it is a folder.
+    pub fn remove_under(&mut self, path: &Path) {
+        self.keep_selection(|list| {
+            list.song_items
+                .retain(|track| !track.path.starts_with(path))
+        });
+    }
+
+    /// The tracks shown in the current [`View`].
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        let listed = match &self.view {
+            View::Playlist { tracks, .. } | View::Trend { tracks, .. } => Some(tracks),
+            _ => None,
+        };
+        let favorites_only = matches!(self.view, View::Favorites);
+        let library = self.song_items.iter().filter(move |track| {
+            listed.is_none() && (!favorites_only || self.favorites.contains(track))
+        });
+        listed.into_iter().flatten().chain(library)
+    }
+
+    /// Liked tracks, marked with ♥ and shown alone in [`View::Favorites`].
+    pub fn set_favorites(&mut self, favorites: Favorites) {
+        self.keep_selection(|list| list.favorites = favorites);
+    }
+
+    pub fn set_view(&mut self, view: View) {
+        self.keep_selection(|list| list.view = view);
+    }
+
+    /// Applies `change` and moves the cursor to where the selected track ended up, or keeps it
+    /// in range if the track is gone from the list.
+    fn keep_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        let selected = self.selected_track().map(|track| track.id);
+        change(self);
+        if let Some(position) =
+            selected.and_then(|id| self.tracks().position(|track| track.id == id))
+        {
+            self.selected_song_index = position;
+        }
+        self.selected_song_index = self
+            .selected_song_index
+            .min(self.tracks().count().saturating_sub(1));
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Number of files the last library scan could not read, shown under the list.
+    pub fn set_scan_errors(&mut self, count: usize) {
+        self.scan_errors = count;
+    }
+
+    /// Files scanned and found while a library scan is running, `None` once it is done.
+    pub fn set_scan_progress(&mut self, progress: Option<(usize, usize)>) {
+        self.scan_progress = progress;
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected_song_index
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.tracks().count() {
+            self.selected_song_index = index;
+        }
+    }
+
+    pub fn selected_track(&self) -> Option<&Track> {
+        self.tracks().nth(self.selected_song_index)
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected_song_index = self.selected_song_index.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected_song_index + 1 < self.tracks().count() {
+            self.selected_song_index += 1;
+        }
+    }
+
+    fn format_duration(duration: &Duration) -> String {
+        let secs = duration.as_secs();
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+
+    fn border_style(&self) -> Style {
+        if self.focused {
+            Style::default().fg(Color::White)
+        } else {
+            Style::default().fg(Color::DarkGray)
         }
     }
 }
@@ -42,43 +163,104 @@ impl Component for SongList {
         Ok(())
     }
 
- 
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        let area_width = area.width as usize;
 
-    fn update(&mut self, action: Action) -> Result<Option<Action>> {
-        match action {
-            Action::Tick => {
-                // логіка на кожен тік
-            }
-            Action::Render => {
-                // наприклад, запускати звук якщо потрібно
-            }
-            _ => {}
-        }
-        Ok(None)
-    }
+        let mut items: Vec<ListItem> = self
+            .tracks()
+            .map(|track| {
+                let liked = self.favorites.contains(track);
+                let left = track.display_name();
+                let album = match (track.album(), track.tags.year) {
+                    (Some(album), Some(year)) => format!(" · {album} ({year})"),
+                    (Some(album), None) => format!(" · {album}"),
+                    _ => String::new(),
+                };
+                let right = Self::format_duration(&track.duration);
 
-    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
-        let items: Vec<ListItem> = self.song_items.iter().map(|(title, duration)| {
-            let padded_title = format!("{:<30}", title);
-            let spans = Line::from(vec![
-                Span::raw(padded_title),
-                Span::styled(duration, Style::default().fg(Color::Gray)),
-            ]);
-            ListItem::new(spans)
-        }).collect();
+                // Загальна довжина без пробілів
+                let total_len = left.chars().count() + album.chars().count() + right.len() + 2;
+                let space = if area_width > total_len + 4 {
+                    area_width - total_len - 4 // залишаємо трохи місця на "➤ " та рамки
+                } else {
+                    1
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        if liked { "♥ " } else { "  " },
+                        Style::default().fg(Color::Red),
+                    ),
+                    Span::raw(left),
+                    Span::styled(album, Style::default().fg(Color::DarkGray)),
+                    Span::raw(" ".repeat(space)),
+                    Span::styled(right, Style::default().fg(Color::Gray)),
+                ]))
+            })
+            .collect();
+        let empty = items.is_empty();
+        let placeholder = match &self.view {
+            View::Favorites => "Тут з'являться вподобані пісні (l — вподобати)",
+            View::Playlist { .. } => "Список порожній (p — додати пісню до списку)",
+            View::Trend {
+                trend: Trend::RecentlyAdded,
+                ..
+            } => "Бібліотека поки порожня",
+            View::Trend { .. } => "Тут з'являться пісні, які ви слухаєте",
+            View::All => "",
+        };
+        if empty && !placeholder.is_empty() {
+            items.push(ListItem::new(Span::styled(
+                placeholder,
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
 
         let mut state = ListState::default();
-        state.select(Some(self.selected_song_index));
+        state.select((!empty).then_some(self.selected_song_index));
+
+        let title = match &self.view {
+            View::All => "Пісні".to_string(),
+            View::Favorites => "Улюблені".to_string(),
+            View::Playlist { name, .. } => format!("♫ {name}"),
+            View::Trend { trend, .. } => format!("Тренди · {}", trend.label()),
+        };
+        let mut block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(self.border_style());
+        if let Some((scanned, total)) = self.scan_progress {
+            let percent = (scanned * 100).checked_div(total).unwrap_or(0);
+            block = block.title(
+                Line::from(format!(" ⟳ Сканування {scanned}/{total} ({percent}%) "))
+                    .style(Style::default().fg(Color::Cyan))
+                    .right_aligned(),
+            );
+        }
+        if self.scan_errors > 0 {
+            block = block.title_bottom(
+                Line::from(format!(
+                    " ⚠ Не вдалося прочитати файлів: {} (e — деталі) ",
+                    self.scan_errors
+                ))
+                .style(Style::default().fg(Color::Yellow)),
+            );
+        }
+
+        if self.focused && matches!(self.view, View::Playlist { .. }) {
+            block = block.title_bottom(
+                Line::from(" x прибрати · [ ] порядок ")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .right_aligned(),
+            );
+        }
 
         let list = List::new(items)
-            .block(Block::default()
-                .title("Пісні")
-                .borders(Borders::ALL)
-                .border_style(self.border_style))
+            .block(block)
             .highlight_style(Style::default().bg(Color::Blue).fg(Color::White))
             .highlight_symbol("➤ ");
 
         frame.render_stateful_widget(list, area, &mut state);
         Ok(())
     }
-}
\ No newline at end of file
+}
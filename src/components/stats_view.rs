@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::history::stats::Stats;
+
+/// How many days the bar chart shows; the sparkline covers all of [`Stats::daily`].
+const CHART_DAYS: usize = 14;
+
+/// Listening statistics drawn over the whole screen.
+#[derive(Debug, Default)]
+pub struct StatsView {
+    stats: Stats,
+}
+
+impl StatsView {
+    pub fn new(stats: Stats) -> Self {
+        Self { stats }
+    }
+
+    pub fn draw(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .title("Статистика")
+            .title_bottom(
+                Line::from(" t / Esc — закрити ").style(Style::default().fg(Color::DarkGray)),
+            )
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow));
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Min(5),
+                Constraint::Length(10),
+                Constraint::Length(5),
+            ])
+            .split(inner);
+
+        self.render_summary(frame, chunks[0]);
+        self.render_tops(frame, chunks[1]);
+        self.render_chart(frame, chunks[2]);
+        self.render_sparkline(frame, chunks[3]);
+    }
+
+    fn render_summary(&self, frame: &mut Frame, area: Rect) {
+        let today = self.stats.daily.last().cloned().unwrap_or_default();
+        let summary = format!(
+            " Прослухано: {} · Прослуховувань: {} · Сьогодні: {}",
+            format_listened(self.stats.listened),
+            self.stats.plays,
+            format_listened(today.listened),
+        );
+        frame.render_widget(Paragraph::new(summary), area);
+    }
+
+    fn render_tops(&self, frame: &mut Frame, area: Rect) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 3); 3])
+            .split(area);
+        let tops = [
+            ("Треки", &self.stats.tracks),
+            ("Виконавці", &self.stats.artists),
+            ("Альбоми", &self.stats.albums),
+        ];
+        for ((title, top), area) in tops.into_iter().zip(columns.iter()) {
+            let block = Block::default().title(title).borders(Borders::ALL);
+            if top.is_empty() {
+                let placeholder = Paragraph::new("Ще нічого не прослухано")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .block(block);
+                frame.render_widget(placeholder, *area);
+                continue;
+            }
+            let items: Vec<ListItem> = top
+                .iter()
+                .map(|(name, count)| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!("{count:>4}× "), Style::default().fg(Color::Gray)),
+                        Span::raw(name.as_str()),
+                    ]))
+                })
+                .collect();
+            frame.render_widget(List::new(items).block(block), *area);
+        }
+    }
+
+    /// Minutes listened on each of the last [`CHART_DAYS`] days.
+    fn render_chart(&self, frame: &mut Frame, area: Rect) {
+        let days = &self.stats.daily[self.stats.daily.len().saturating_sub(CHART_DAYS)..];
+        let bars: Vec<Bar> = days
+            .iter()
+            .map(|day| {
+                let minutes = day.listened.as_secs() / 60;
+                Bar::default()
+                    .value(minutes)
+                    .label(Line::from(day.label.as_str()))
+                    .text_value(minutes.to_string())
+            })
+            .collect();
+        // Кожен стовпчик не вужчий за підпис дати "17.10"
+        let width = area.width.saturating_sub(2) / CHART_DAYS.max(1) as u16;
+        let chart = BarChart::default()
+            .block(
+                Block::default()
+                    .title("Хвилин за день")
+                    .borders(Borders::ALL),
+            )
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(width.saturating_sub(1).max(5))
+            .bar_gap(1)
+            .bar_style(Style::default().fg(Color::Green))
+            .value_style(Style::default().fg(Color::Black).bg(Color::Green));
+        frame.render_widget(chart, area);
+    }
+
+    /// Plays per day over the whole [`Stats::daily`], today at the right edge.
+    fn render_sparkline(&self, frame: &mut Frame, area: Rect) {
+        // Якщо днів більше, ніж колонок, обрізаються найстаріші
+        let plays: Vec<u64> = self.stats.daily.iter().rev().map(|day| day.plays).collect();
+        let sparkline = Sparkline::default()
+            .block(
+                Block::default()
+                    .title(format!(
+                        "Прослуховування за {} днів",
+                        self.stats.daily.len()
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .data(&plays)
+            .direction(RenderDirection::RightToLeft)
+            .style(Style::default().fg(Color::Cyan));
+        frame.render_widget(sparkline, area);
+    }
+}
+
+/// "2 год 5 хв", "12 хв" or "40 с".
+fn format_listened(listened: Duration) -> String {
+    let secs = listened.as_secs();
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{secs} с"),
+        (0, _) => format!("{minutes} хв"),
+        _ => format!("{hours} год {minutes} хв"),
+    }
+}
@@ -1,11 +1,11 @@
 #![allow(dead_code)] // Remove this once you start using the code
 
-use std::{collections::HashMap, env, path::PathBuf};
+use std::{collections::HashMap, env, path::PathBuf, str::FromStr, time::Duration};
 
 use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use derive_deref::{Deref, DerefMut};
-use directories::ProjectDirs;
+use directories::{BaseDirs, ProjectDirs};
 use lazy_static::lazy_static;
 use ratatui::style::{Color, Modifier, Style};
 use serde::{Deserialize, de::Deserializer};
@@ -23,11 +23,95 @@ pub struct AppConfig {
     pub config_dir: PathBuf,
 }
 
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct LibraryConfig {
+    /// Directories scanned for music. `~` and environment variables are expanded on load.
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+}
+
+/// How a volume level maps to the output amplitude.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeCurve {
+    /// The level is the amplitude.
+    #[default]
+    Linear,
+    /// Perceptual: the level spans 60 dB, so every step sounds about as loud as the last one.
+    Logarithmic,
+}
+
+/// Where audio is played.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputBackend {
+    /// The default sound card.
+    #[default]
+    Device,
+    /// No sound at all, but tracks still play in real time; for SSH sessions and CI.
+    Null,
+    /// Record everything that plays into a WAV file.
+    Wav(PathBuf),
+}
+
+impl FromStr for OutputBackend {
+    type Err = String;
+
+    /// Parses `device`, `null` or `wav:PATH`.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "device" => Ok(Self::Device),
+            "null" => Ok(Self::Null),
+            _ => match raw.strip_prefix("wav:") {
+                Some(path) if !path.is_empty() => Ok(Self::Wav(expand_path(path))),
+                _ => Err(format!(
+                    "unknown output {raw:?}, expected device, null or wav:PATH"
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    /// How much one volume up/down press changes the level, from 0.0 to 1.0.
+    pub volume_step: f32,
+    pub volume_curve: VolumeCurve,
+    pub output: OutputBackend,
+    /// Seconds one track fades into the next while crossfade is on, from 0 to 12.
+    pub crossfade: f32,
+}
+
+impl PlayerConfig {
+    pub const MAX_CROSSFADE: f32 = 12.0;
+
+    pub fn crossfade(&self) -> Duration {
+        Duration::try_from_secs_f32(self.crossfade.clamp(0.0, Self::MAX_CROSSFADE))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            volume_step: 0.05,
+            volume_curve: VolumeCurve::default(),
+            output: OutputBackend::default(),
+            crossfade: 6.0,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct Config {
     #[serde(default, flatten)]
     pub config: AppConfig,
     #[serde(default)]
+    pub library: LibraryConfig,
+    #[serde(default)]
+    pub player: PlayerConfig,
+    #[serde(default)]
     pub keybindings: KeyBindings,
     #[serde(default)]
     pub styles: Styles,
@@ -91,31 +175,83 @@ impl Config {
                 user_styles.entry(style_key.clone()).or_insert(*style);
             }
         }
+        if cfg.library.roots.is_empty() {
+            cfg.library.roots = default_config.library.roots;
+        }
+        cfg.library.roots = cfg
+            .library
+            .roots
+            .iter()
+            .map(|root| expand_path(&root.to_string_lossy()))
+            .collect();
+        if let OutputBackend::Wav(path) = &mut cfg.player.output {
+            *path = expand_path(&path.to_string_lossy());
+        }
 
         Ok(cfg)
     }
 }
 
+/// Expands a leading `~` and `$VAR` / `${VAR}` references, then makes the path absolute so it
+/// does not depend on the working directory. Unknown variables are left as is.
+pub fn expand_path(raw: &str) -> PathBuf {
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    let expanded = expand_env_vars(raw, |name| env::var(name).ok());
+    let path = match (expanded.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(&expanded),
+    };
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// Replaces `$VAR` / `${VAR}` with what `lookup` returns for `VAR`.
+fn expand_env_vars(raw: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, tail) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        match lookup(name) {
+            Some(value) if !name.is_empty() => result.push_str(&value),
+            _ => result.push_str(&rest[start..rest.len() - tail.len()]),
+        }
+        rest = tail;
+    }
+    result.push_str(rest);
+    result
+}
+
 pub fn get_data_dir() -> PathBuf {
-    let directory = if let Some(s) = DATA_FOLDER.clone() {
+    if let Some(s) = DATA_FOLDER.clone() {
         s
     } else if let Some(proj_dirs) = project_directory() {
         proj_dirs.data_local_dir().to_path_buf()
     } else {
         PathBuf::from(".").join(".data")
-    };
-    directory
+    }
 }
 
 pub fn get_config_dir() -> PathBuf {
-    let directory = if let Some(s) = CONFIG_FOLDER.clone() {
+    if let Some(s) = CONFIG_FOLDER.clone() {
         s
     } else if let Some(proj_dirs) = project_directory() {
         proj_dirs.config_local_dir().to_path_buf()
     } else {
         PathBuf::from(".").join(".config")
-    };
-    directory
+    }
 }
 
 fn project_directory() -> Option<ProjectDirs> {
@@ -297,8 +433,7 @@ pub fn parse_key_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
     }
     let raw = if !raw.contains("><") {
         let raw = raw.strip_prefix('<').unwrap_or(raw);
-        let raw = raw.strip_prefix('>').unwrap_or(raw);
-        raw
+        raw.strip_prefix('>').unwrap_or(raw)
     } else {
         raw
     };
@@ -514,6 +649,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_expand_env_vars() {
+        let vars = HashMap::from([("MUSIC", "/srv/music"), ("GENRE", "jazz")]);
+        let expand = |raw| expand_env_vars(raw, |name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(expand("$MUSIC/rock"), "/srv/music/rock");
+        assert_eq!(expand("${MUSIC}/${GENRE}"), "/srv/music/jazz");
+        assert_eq!(expand("$MUSIC_$GENRE"), "$MUSIC_jazz");
+        assert_eq!(expand("/tmp/$UNSET/${UNSET}"), "/tmp/$UNSET/${UNSET}");
+        assert_eq!(expand("${MUSIC/$/x"), "${MUSIC/$/x");
+    }
+
+    #[test]
+    fn test_expand_path_home() {
+        let home = BaseDirs::new().unwrap().home_dir().to_path_buf();
+        assert_eq!(expand_path("~"), home);
+        assert_eq!(expand_path("~/Music"), home.join("Music"));
+        assert!(expand_path("relative/dir").is_absolute());
+    }
+
     #[test]
     fn test_simple_keys() {
         assert_eq!(
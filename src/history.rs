@@ -0,0 +1,172 @@
+//! Play history: every start, skip and completion of a track, appended to a log in the data
+//! directory. The trends in the sidebar and the statistics screen are computed from it.
+
+pub mod stats;
+pub mod trends;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    library::{ContentHash, Track},
+    storage,
+};
+
+/// JSON Lines file in the data directory, one [`HistoryEntry`] per line.
+pub const FILE_NAME: &str = "history.jsonl";
+
+/// A skipped track still counts as played once this much of it was heard.
+pub const PLAY_THRESHOLD: Duration = Duration::from_secs(30);
+
+pub const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayEvent {
+    /// The track began to sound.
+    Start,
+    /// Playback moved on before the track ended: another track was picked, playback was stopped
+    /// or the app closed.
+    Skip,
+    /// The track played to its end.
+    Complete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub event: PlayEvent,
+    /// The track by content, as favorites and playlists remember it.
+    pub track: ContentHash,
+    pub path: PathBuf,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    /// How much of the track was heard, not counting parts jumped over; zero for a start.
+    pub listened: Duration,
+}
+
+impl HistoryEntry {
+    pub fn new(event: PlayEvent, track: &Track, listened: Duration) -> Self {
+        Self {
+            event,
+            track: track.content_hash,
+            path: track.path.clone(),
+            at: now(),
+            listened,
+        }
+    }
+
+    /// Whether this entry ends a play worth counting: the track was heard to the end or long
+    /// enough before it was skipped.
+    pub fn is_play(&self) -> bool {
+        match self.event {
+            PlayEvent::Start => false,
+            PlayEvent::Skip => self.listened >= PLAY_THRESHOLD,
+            PlayEvent::Complete => true,
+        }
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// The whole log, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            entries: storage::load_json_lines(path)?,
+        })
+    }
+
+    /// Writes `entry` to the end of the log file and keeps it in memory.
+    pub fn append(&mut self, path: &Path, entry: HistoryEntry) -> Result<()> {
+        storage::append_json_line(path, &entry)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// How many times each track was played from `since` (inclusive) to `until` (exclusive).
+    pub fn play_counts(&self, since: u64, until: u64) -> HashMap<ContentHash, usize> {
+        let mut counts = HashMap::new();
+        for entry in &self.entries {
+            if entry.is_play() && (since..until).contains(&entry.at) {
+                *counts.entry(entry.track).or_default() += 1;
+            }
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_log_counts_only_plays() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let entry = |event, listened, at| HistoryEntry {
+            event,
+            track: ContentHash(1),
+            path: PathBuf::from("/a.mp3"),
+            at,
+            listened: Duration::from_secs(listened),
+        };
+
+        let mut log = String::new();
+        for entry in [
+            entry(PlayEvent::Start, 0, 10),
+            entry(PlayEvent::Complete, 200, 11),
+            entry(PlayEvent::Skip, 5, 12),
+            entry(PlayEvent::Skip, 40, 13),
+            entry(PlayEvent::Complete, 200, 99),
+        ] {
+            log += &serde_json::to_string(&entry).unwrap();
+            log.push('\n');
+        }
+        // A line cut short by a crash is skipped
+        log += "{\"event\":\"sta";
+        fs::write(&path, log).unwrap();
+
+        let history = History::load(&path).unwrap();
+        assert_eq!(history.entries.len(), 5);
+        assert_eq!(
+            history.play_counts(0, 50),
+            HashMap::from([(ContentHash(1), 2)])
+        );
+        assert!(history.play_counts(100, 200).is_empty());
+    }
+
+    #[test]
+    fn test_append_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data").join(FILE_NAME);
+        let track = Track::new(PathBuf::from("/a.mp3"));
+
+        let mut history = History::default();
+        for event in [PlayEvent::Start, PlayEvent::Complete] {
+            let entry = HistoryEntry::new(event, &track, Duration::from_secs(1));
+            history.append(&path, entry).unwrap();
+        }
+        assert_eq!(History::load(&path).unwrap().entries, history.entries);
+    }
+}
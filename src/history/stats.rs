@@ -0,0 +1,219 @@
+//! Listening statistics over the whole play history: top tracks, artists and albums, total time
+//! and how much was listened each day.
+
+use std::{cmp::Reverse, collections::HashMap, time::Duration};
+
+use super::{DAY, History};
+use crate::library::Track;
+
+/// Longest a top list gets.
+const TOP: usize = 10;
+/// How many days [`Stats::daily`] goes back, today included.
+pub const DAYS: usize = 90;
+
+/// One calendar day of [`Stats::daily`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Day {
+    /// Day and month, e.g. "17.10".
+    pub label: String,
+    pub listened: Duration,
+    pub plays: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Time heard over the whole history.
+    pub listened: Duration,
+    pub plays: usize,
+    /// Names with play counts, most played first.
+    pub tracks: Vec<(String, usize)>,
+    pub artists: Vec<(String, usize)>,
+    pub albums: Vec<(String, usize)>,
+    /// The last [`DAYS`] days, oldest first, ending today.
+    pub daily: Vec<Day>,
+}
+
+impl Stats {
+    /// Statistics as of `now`; days start at local midnight, `offset` seconds ahead of UTC.
+    /// Plays of tracks no longer in the library are named after their file.
+    pub fn compute(history: &History, library: &[Track], now: u64, offset: i64) -> Self {
+        let by_hash: HashMap<_, _> = library
+            .iter()
+            .rev()
+            .map(|track| (track.content_hash, track))
+            .collect();
+        let day_of = |at: u64| at.saturating_add_signed(offset) / DAY;
+        let today = day_of(now);
+        let first_day = today.saturating_sub(DAYS as u64 - 1);
+        let mut daily: Vec<Day> = (first_day..=today)
+            .map(|day| Day {
+                label: date_label(day),
+                ..Day::default()
+            })
+            .collect();
+
+        let mut stats = Self::default();
+        let (mut tracks, mut artists, mut albums) =
+            (HashMap::new(), HashMap::new(), HashMap::new());
+        for entry in &history.entries {
+            stats.listened += entry.listened;
+            let day = day_of(entry.at)
+                .checked_sub(first_day)
+                .and_then(|day| daily.get_mut(day as usize));
+            if let Some(day) = day {
+                day.listened += entry.listened;
+                day.plays += u64::from(entry.is_play());
+            }
+            if !entry.is_play() {
+                continue;
+            }
+            stats.plays += 1;
+            let Some(track) = by_hash.get(&entry.track) else {
+                let name = entry.path.file_stem().unwrap_or_default();
+                *tracks
+                    .entry(name.to_string_lossy().into_owned())
+                    .or_default() += 1;
+                continue;
+            };
+            *tracks.entry(track.display_name()).or_default() += 1;
+            if let Some(artist) = track.artist() {
+                *artists.entry(artist.to_string()).or_default() += 1;
+            }
+            // Назви на кшталт "Greatest Hits" повторюються, тож альбом — разом із виконавцем
+            if let Some(album) = track.album() {
+                let name = match track.artist() {
+                    Some(artist) => format!("{album} ({artist})"),
+                    None => album.to_string(),
+                };
+                *albums.entry(name).or_default() += 1;
+            }
+        }
+        stats.tracks = top(tracks);
+        stats.artists = top(artists);
+        stats.albums = top(albums);
+        stats.daily = daily;
+        stats
+    }
+}
+
+fn top(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| (Reverse(a.1), &a.0).cmp(&(Reverse(b.1), &b.0)));
+    counts.truncate(TOP);
+    counts
+}
+
+/// "17.10" for a day counted from the Unix epoch.
+fn date_label(day: u64) -> String {
+    // Перетворення днів від 1970-01-01 на дату григоріанського календаря (алгоритм Хіннанта)
+    let z = day + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    format!("{day_of_month:02}.{month:02}")
+}
+
+/// How many seconds local time is ahead of UTC at `at`.
+#[cfg(unix)]
+// `c_long` is only 32 bits wide on 32-bit targets
+#[allow(clippy::useless_conversion)]
+pub fn utc_offset(at: u64) -> i64 {
+    let time = at as libc::time_t;
+    // SAFETY: `tm` is plain data that localtime_r fills in; both pointers are valid for the call.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    i64::from(tm.tm_gmtoff)
+}
+
+#[cfg(not(unix))]
+pub fn utc_offset(_at: u64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        history::{HistoryEntry, PlayEvent},
+        library::ContentHash,
+    };
+
+    /// 2024-10-17 00:00 UTC.
+    const NOW: u64 = 1_729_123_200;
+
+    fn track(name: &str, hash: u64, artist: &str, album: &str) -> Track {
+        let mut track = Track::new(PathBuf::from(format!("/{name}.mp3")));
+        track.content_hash = ContentHash(hash);
+        track.tags.title = Some(name.to_string());
+        track.tags.artist = Some(artist.to_string());
+        track.tags.album = Some(album.to_string());
+        track
+    }
+
+    #[test]
+    fn test_date_label() {
+        assert_eq!(date_label(0), "01.01");
+        assert_eq!(date_label(NOW / DAY), "17.10");
+        // 2024 is a leap year
+        assert_eq!(date_label(19_782), "29.02");
+    }
+
+    #[test]
+    fn test_compute() {
+        let library = vec![
+            track("Пісня", 1, "Гурт", "Перший"),
+            track("Інша", 2, "Гурт", "Другий"),
+        ];
+        let gone = Track::new(PathBuf::from("/music/Зникла.mp3"));
+        let mut history = History::default();
+        let mut log = |event, track: &Track, secs, at| {
+            history.entries.push(HistoryEntry {
+                at,
+                ..HistoryEntry::new(event, track, Duration::from_secs(secs))
+            });
+        };
+        log(PlayEvent::Start, &library[0], 0, NOW - DAY);
+        log(PlayEvent::Complete, &library[0], 200, NOW - DAY);
+        log(PlayEvent::Complete, &library[0], 200, NOW + 60);
+        log(PlayEvent::Skip, &library[1], 10, NOW - 3600);
+        log(PlayEvent::Complete, &library[1], 100, NOW + 180);
+        log(PlayEvent::Complete, &gone, 50, NOW - 200 * DAY);
+
+        // Two hours ahead of UTC: 23:00 UTC the day before is already today
+        let stats = Stats::compute(&history, &library, NOW + 3600, 2 * 3600);
+        assert_eq!(stats.listened, Duration::from_secs(560));
+        assert_eq!(stats.plays, 4);
+        assert_eq!(
+            stats.tracks,
+            vec![
+                ("Гурт — Пісня".to_string(), 2),
+                ("Гурт — Інша".to_string(), 1),
+                ("Зникла".to_string(), 1),
+            ]
+        );
+        assert_eq!(stats.artists, vec![("Гурт".to_string(), 3)]);
+        assert_eq!(stats.albums[0], ("Перший (Гурт)".to_string(), 2));
+
+        assert_eq!(stats.daily.len(), DAYS);
+        let today = stats.daily.last().unwrap();
+        assert_eq!(today.label, "17.10");
+        assert_eq!(today.listened, Duration::from_secs(310));
+        assert_eq!(today.plays, 2);
+        assert_eq!(stats.daily[DAYS - 2].plays, 1);
+    }
+}
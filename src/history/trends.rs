@@ -0,0 +1,153 @@
+//! The "Тренди" lists: what was played most lately, what is played more than before and what
+//! was added to the library last.
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use super::{DAY, History};
+use crate::library::{ContentHash, Track};
+
+/// Longest a trend list gets.
+const LIMIT: usize = 50;
+const WEEK: u64 = 7 * DAY;
+const MONTH: u64 = 30 * DAY;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    /// Played most over the last 7 days.
+    Week,
+    /// Played most over the last 30 days.
+    Month,
+    /// Played more this week than the week before, by the largest margin first.
+    Rising,
+    /// Newest files in the library.
+    RecentlyAdded,
+}
+
+impl Trend {
+    /// In the order they are listed in the sidebar.
+    pub const ALL: [Self; 4] = [Self::Week, Self::Month, Self::Rising, Self::RecentlyAdded];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Week => "Найчастіше за тиждень",
+            Self::Month => "Найчастіше за місяць",
+            Self::Rising => "На підйомі",
+            Self::RecentlyAdded => "Нещодавно додані",
+        }
+    }
+
+    /// The tracks of the trend as of `now` (seconds since the Unix epoch). Played tracks that are
+    /// no longer in the library are left out.
+    pub fn tracks(self, history: &History, library: &[Track], now: u64) -> Vec<Track> {
+        let ranked = match self {
+            Self::Week => most_played(history.play_counts(now.saturating_sub(WEEK), u64::MAX)),
+            Self::Month => most_played(history.play_counts(now.saturating_sub(MONTH), u64::MAX)),
+            Self::Rising => {
+                let this_week = history.play_counts(now.saturating_sub(WEEK), u64::MAX);
+                let last_week =
+                    history.play_counts(now.saturating_sub(2 * WEEK), now.saturating_sub(WEEK));
+                let mut rising: Vec<_> = this_week
+                    .into_iter()
+                    .filter_map(|(track, count)| {
+                        let before = last_week.get(&track).copied().unwrap_or_default();
+                        (count > before).then(|| (track, count - before, count))
+                    })
+                    .collect();
+                rising
+                    .sort_by_key(|&(track, delta, count)| (Reverse(delta), Reverse(count), track));
+                rising.into_iter().map(|(track, ..)| track).collect()
+            }
+            Self::RecentlyAdded => {
+                let mut added: Vec<_> = library.iter().filter(|track| track.added > 0).collect();
+                added.sort_by_key(|track| (Reverse(track.added), &track.path));
+                return added.into_iter().take(LIMIT).cloned().collect();
+            }
+        };
+
+        let by_hash: HashMap<_, _> = library
+            .iter()
+            .rev()
+            .map(|track| (track.content_hash, track))
+            .collect();
+        ranked
+            .into_iter()
+            .filter_map(|hash| by_hash.get(&hash).map(|&track| track.clone()))
+            .take(LIMIT)
+            .collect()
+    }
+}
+
+fn most_played(counts: HashMap<ContentHash, usize>) -> Vec<ContentHash> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by_key(|&(track, count)| (Reverse(count), track));
+    counts.into_iter().map(|(track, _)| track).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::history::{HistoryEntry, PlayEvent};
+
+    const NOW: u64 = 100 * DAY;
+
+    fn track(name: &str, hash: u64, added: u64) -> Track {
+        Track {
+            content_hash: ContentHash(hash),
+            added,
+            ..Track::new(PathBuf::from(name))
+        }
+    }
+
+    fn names(tracks: Vec<Track>) -> Vec<String> {
+        tracks.iter().map(Track::title).collect()
+    }
+
+    #[test]
+    fn test_trends() {
+        let library = vec![
+            track("/a.mp3", 1, NOW - 40 * DAY),
+            track("/b.mp3", 2, NOW - DAY),
+            track("/c.mp3", 3, 0),
+        ];
+        let mut history = History::default();
+        let mut play = |track: &Track, days_ago: u64, times: usize| {
+            for _ in 0..times {
+                history.entries.push(HistoryEntry {
+                    event: PlayEvent::Complete,
+                    track: track.content_hash,
+                    path: track.path.clone(),
+                    at: NOW - days_ago * DAY,
+                    listened: Duration::from_secs(200),
+                });
+            }
+        };
+        // a: played most overall, but less than the week before; b: new this week
+        play(&library[0], 10, 5);
+        play(&library[0], 1, 3);
+        play(&library[1], 2, 2);
+        // Gone from the library
+        play(&track("/gone.mp3", 9, 0), 1, 9);
+
+        assert_eq!(
+            names(Trend::Week.tracks(&history, &library, NOW)),
+            ["a", "b"]
+        );
+        assert_eq!(
+            names(Trend::Month.tracks(&history, &library, NOW)),
+            ["a", "b"]
+        );
+        assert_eq!(names(Trend::Rising.tracks(&history, &library, NOW)), ["b"]);
+        assert_eq!(
+            names(Trend::Rising.tracks(&history, &library, NOW + 9 * DAY)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            names(Trend::RecentlyAdded.tracks(&history, &library, NOW)),
+            ["b", "a"]
+        );
+    }
+}
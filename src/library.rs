@@ -0,0 +1,96 @@
+//! Music library: discovering audio files under the configured roots, reading their properties
+//! and indexing them by [`TrackId`].
+
+use std::{collections::HashMap, path::Path};
+
+pub mod cache;
+pub mod duration;
+pub mod favorites;
+pub mod playlists;
+pub mod scanner;
+pub mod tags;
+pub mod track;
+pub mod watcher;
+
+pub use track::{ContentHash, Tags, Track, TrackId};
+
+/// All known tracks, in scan order, indexed by id.
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    tracks: Vec<Track>,
+    index: HashMap<TrackId, usize>,
+}
+
+impl Library {
+    /// Adds a track, replacing the previous version of the same file.
+    pub fn insert(&mut self, track: Track) {
+        match self.index.get(&track.id) {
+            Some(&position) => self.tracks[position] = track,
+            None => {
+                self.index.insert(track.id, self.tracks.len());
+                self.tracks.push(track);
+            }
+        }
+    }
+
+    /// Removes the track at `path`, or every track inside it if it is a folder.
+    pub fn remove_under(&mut self, path: &Path) {
+        self.tracks.retain(|track| !track.path.starts_with(path));
+        self.index = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(position, track)| (track.id, position))
+            .collect();
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn track(path: &str) -> Track {
+        Track::new(PathBuf::from(path))
+    }
+
+    #[test]
+    fn test_insert_replaces_same_path() {
+        let mut library = Library::default();
+        library.insert(track("/music/a.mp3"));
+        library.insert(track("/music/b.mp3"));
+        let mut updated = track("/music/a.mp3");
+        updated.tags.title = Some("A".into());
+        library.insert(updated);
+
+        assert_eq!(library.tracks().len(), 2);
+        assert_eq!(library.tracks()[0].title(), "A");
+        assert_eq!(
+            library.tracks()[0].id,
+            TrackId::from_path(&PathBuf::from("/music/a.mp3"))
+        );
+    }
+
+    #[test]
+    fn test_remove_folder() {
+        let mut library = Library::default();
+        library.insert(track("/music/Artist/a.mp3"));
+        library.insert(track("/music/Other/b.mp3"));
+        library.insert(track("/music/Artist/Album/c.mp3"));
+
+        library.remove_under(Path::new("/music/Artist"));
+        assert_eq!(library.tracks().len(), 1);
+        assert_eq!(library.tracks()[0].title(), "b");
+
+        // The index still points at the right positions.
+        library.insert(track("/music/Other/b.mp3"));
+        assert_eq!(library.tracks().len(), 1);
+    }
+}
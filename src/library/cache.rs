@@ -0,0 +1,312 @@
+//! On-disk index of already probed files, so unchanged files are not decoded again on the next
+//! launch.
+//!
+//! The cache is a versioned JSON document. When the layout changes, bump [`VERSION`] and append
+//! a step to [`MIGRATIONS`]; caches written by older versions are upgraded on load, and caches
+//! from newer or unknown versions are ignored and rebuilt.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::Track;
+use crate::storage;
+
+pub const FILE_NAME: &str = "library.json";
+
+/// Current layout version of the cache file.
+const VERSION: u64 = 5;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`.
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    drop_estimated_durations,
+    add_ignored_files,
+    drop_unhashed_tracks,
+    add_added_times,
+];
+
+/// Version 2 measures durations from MP3 headers and packet timestamps; forget the tracks whose
+/// duration came from a bitrate estimate or was unknown so they are probed again.
+fn drop_estimated_durations(document: &mut Value) {
+    if let Some(entries) = document["entries"].as_array_mut() {
+        entries.retain(|entry| {
+            let track = &entry["track"];
+            let zero = track["duration"]["secs"] == 0 && track["duration"]["nanos"] == 0;
+            track["format"] != "MP3" && !zero
+        });
+    }
+}
+
+/// Version 3 remembers files that turned out not to be audio, so they are not probed again.
+fn add_ignored_files(document: &mut Value) {
+    document["ignored"] = Value::Array(Vec::new());
+}
+
+/// Version 4 identifies tracks by a hash of their content, which takes reading the file; probe
+/// every track again to get it.
+fn drop_unhashed_tracks(document: &mut Value) {
+    document["entries"] = Value::Array(Vec::new());
+}
+
+/// Version 5 records when each file appeared. Cached tracks take it from the stamp's
+/// modification time, which is as close as the cache gets without reading the files again.
+fn add_added_times(document: &mut Value) {
+    if let Some(entries) = document["entries"].as_array_mut() {
+        for entry in entries {
+            let modified = entry["stamp"]["modified"].as_u64().unwrap_or_default();
+            entry["track"]["added"] = Value::from(modified / 1_000_000_000);
+        }
+    }
+}
+
+/// Size and modification time of a file, used to tell whether it changed since it was probed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_nanos() as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    stamp: FileStamp,
+    track: Track,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IgnoredFile {
+    path: PathBuf,
+    stamp: FileStamp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u64,
+    entries: Vec<CacheEntry>,
+    ignored: Vec<IgnoredFile>,
+}
+
+/// Probed tracks keyed by absolute path, plus the files that are not audio at all.
+#[derive(Debug, Clone, Default)]
+pub struct LibraryCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    ignored: HashMap<PathBuf, FileStamp>,
+}
+
+impl LibraryCache {
+    /// Loads the cache, upgrading older layouts. A missing file yields an empty cache.
+    pub fn load(path: &Path) -> Result<Self> {
+        let Some(mut document) = storage::load_json::<Value>(path)? else {
+            return Ok(Self::default());
+        };
+        migrate(&mut document)?;
+        let file: CacheFile = serde_json::from_value(document)?;
+        let entries = file
+            .entries
+            .into_iter()
+            .map(|entry| (entry.track.path.clone(), entry))
+            .collect();
+        let ignored = file
+            .ignored
+            .into_iter()
+            .map(|file| (file.path, file.stamp))
+            .collect();
+        Ok(Self { entries, ignored })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut entries: Vec<_> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| a.track.path.cmp(&b.track.path));
+        let mut ignored: Vec<_> = self
+            .ignored
+            .iter()
+            .map(|(path, &stamp)| IgnoredFile {
+                path: path.clone(),
+                stamp,
+            })
+            .collect();
+        ignored.sort_by(|a, b| a.path.cmp(&b.path));
+        storage::save_json(
+            path,
+            &CacheFile {
+                version: VERSION,
+                entries,
+                ignored,
+            },
+        )
+    }
+
+    /// Returns the cached track for `path` if the file has not changed since it was probed.
+    pub fn get(&self, path: &Path, stamp: FileStamp) -> Option<&Track> {
+        self.entries
+            .get(path)
+            .filter(|entry| entry.stamp == stamp)
+            .map(|entry| &entry.track)
+    }
+
+    pub fn insert(&mut self, stamp: FileStamp, track: Track) {
+        self.ignored.remove(&track.path);
+        self.entries
+            .insert(track.path.clone(), CacheEntry { stamp, track });
+    }
+
+    /// Whether `path` was found not to be audio and has not changed since.
+    pub fn is_ignored(&self, path: &Path, stamp: FileStamp) -> bool {
+        self.ignored.get(path) == Some(&stamp)
+    }
+
+    /// Remembers that `path` is not an audio file.
+    pub fn ignore(&mut self, path: PathBuf, stamp: FileStamp) {
+        self.entries.remove(&path);
+        self.ignored.insert(path, stamp);
+    }
+
+    /// Forgets `path` and, if it was a folder, every file inside it.
+    pub fn remove_under(&mut self, path: &Path) {
+        self.entries.retain(|file, _| !file.starts_with(path));
+        self.ignored.retain(|file, _| !file.starts_with(path));
+    }
+}
+
+fn migrate(document: &mut Value) -> Result<()> {
+    let mut version = document
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| eyre!("library cache has no version"))?;
+    if version == 0 || version > VERSION {
+        return Err(eyre!("unsupported library cache version {version}"));
+    }
+    while version < VERSION {
+        MIGRATIONS[(version - 1) as usize](document);
+        version += 1;
+        document["version"] = Value::from(version);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let stamp = FileStamp {
+            size: 42,
+            modified: 7,
+        };
+        let mut track = Track::new(PathBuf::from("/music/a.flac"));
+        track.tags.title = Some("A".into());
+
+        let mut cache = LibraryCache::default();
+        cache.insert(stamp, track.clone());
+        cache.ignore(PathBuf::from("/music/cover.jpg"), stamp);
+        cache.save(&path).unwrap();
+
+        let cache = LibraryCache::load(&path).unwrap();
+        assert_eq!(cache.get(&track.path, stamp), Some(&track));
+        assert!(cache.is_ignored(Path::new("/music/cover.jpg"), stamp));
+        let changed = FileStamp { size: 43, ..stamp };
+        assert_eq!(cache.get(&track.path, changed), None);
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        fs::write(&path, r#"{"version": 999, "entries": []}"#).unwrap();
+        assert!(LibraryCache::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_v1_drops_estimated_durations() {
+        let stamp = FileStamp {
+            size: 1,
+            modified: 1,
+        };
+        let entry = |name: &str, format: &str, secs: u64| {
+            let mut track = Track::new(PathBuf::from(name));
+            track.format = format.into();
+            track.duration = std::time::Duration::from_secs(secs);
+            CacheEntry { stamp, track }
+        };
+        let entries = vec![
+            entry("/a.mp3", "MP3", 200),
+            entry("/b.aac", "AAC", 0),
+            entry("/c.flac", "FLAC", 180),
+        ];
+        let mut file = serde_json::json!({ "version": 1, "entries": entries });
+        drop_estimated_durations(&mut file);
+
+        let entries: Vec<CacheEntry> = serde_json::from_value(file["entries"].clone()).unwrap();
+        let paths: Vec<_> = entries.into_iter().map(|entry| entry.track.path).collect();
+        assert_eq!(paths, vec![PathBuf::from("/c.flac")]);
+    }
+
+    #[test]
+    fn test_v3_probes_tracks_again_for_content_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let stamp = FileStamp {
+            size: 1,
+            modified: 1,
+        };
+        let file = serde_json::json!({
+            "version": 3,
+            "entries": [CacheEntry { stamp, track: Track::new(PathBuf::from("/a.flac")) }],
+            "ignored": [IgnoredFile { path: PathBuf::from("/cover.jpg"), stamp }],
+        });
+        fs::write(&path, file.to_string()).unwrap();
+
+        let cache = LibraryCache::load(&path).unwrap();
+        assert!(cache.entries.is_empty());
+        assert!(cache.is_ignored(Path::new("/cover.jpg"), stamp));
+    }
+
+    #[test]
+    fn test_v4_takes_added_times_from_stamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let stamp = FileStamp {
+            size: 1,
+            modified: 1_700_000_000_123_456_789,
+        };
+        let mut track = serde_json::to_value(Track::new(PathBuf::from("/a.flac"))).unwrap();
+        track.as_object_mut().unwrap().remove("added");
+        let file = serde_json::json!({
+            "version": 4,
+            "entries": [{ "stamp": stamp, "track": track }],
+            "ignored": [],
+        });
+        fs::write(&path, file.to_string()).unwrap();
+
+        let cache = LibraryCache::load(&path).unwrap();
+        let track = cache.get(Path::new("/a.flac"), stamp).unwrap();
+        assert_eq!(track.added, 1_700_000_000);
+    }
+}
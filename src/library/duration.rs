@@ -0,0 +1,280 @@
+//! Duration detection that does not trust the container blindly.
+//!
+//! Symphonia reports `n_frames` only when the container stores it; MP3 files without a
+//! Xing/VBRI header get a bitrate based estimate that is wrong for VBR, and raw AAC (ADTS)
+//! streams get nothing at all. For those the header is read directly or, as a last resort,
+//! the packet timestamps are scanned to the end of the file.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use symphonia::core::{
+    codecs::{CODEC_TYPE_MP1, CODEC_TYPE_MP2, CODEC_TYPE_MP3, CodecParameters},
+    errors::Error,
+    formats::FormatReader,
+};
+
+use crate::audio::codecs;
+
+/// How many bytes at the start of an MP3 file are searched for the first frame.
+const MP3_SEARCH_LEN: usize = 64 * 1024;
+
+/// Measures the duration of the default track of an opened file. Consumes packets from
+/// `format`, so it should be the last thing done with the reader.
+pub fn measure(path: &Path, format: &mut dyn FormatReader) -> Duration {
+    let Some(track) = format.default_track() else {
+        return Duration::ZERO;
+    };
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+
+    let is_mpeg_audio = [CODEC_TYPE_MP1, CODEC_TYPE_MP2, CODEC_TYPE_MP3].contains(&params.codec);
+    let known = if is_mpeg_audio {
+        read_mpeg_header(path)
+    } else {
+        from_params(&params, codecs::n_frames(&params))
+    };
+    known
+        .or_else(|| scan_packets(format, track_id, &params))
+        .unwrap_or_default()
+}
+
+fn from_params(params: &CodecParameters, frames: Option<u64>) -> Option<Duration> {
+    let frames = frames?;
+    if let Some(time_base) = params.time_base {
+        let time = time_base.calc_time(frames);
+        return Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac));
+    }
+    let rate = params.sample_rate?;
+    Some(Duration::from_secs_f64(frames as f64 / f64::from(rate)))
+}
+
+/// Walks every packet of the track and returns the end timestamp of the last one.
+fn scan_packets(
+    format: &mut dyn FormatReader,
+    track_id: u32,
+    params: &CodecParameters,
+) -> Option<Duration> {
+    let mut end = None;
+    loop {
+        match format.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => {
+                end = end.max(Some(packet.ts + packet.dur));
+            }
+            Ok(_) => {}
+            Err(Error::ResetRequired) => continue,
+            Err(_) => break,
+        }
+    }
+    from_params(params, end)
+}
+
+fn read_mpeg_header(path: &Path) -> Option<Duration> {
+    let mut file = File::open(path).ok()?;
+    // Skip the ID3v2 tag, which can be large when it holds cover art.
+    let mut id3 = [0u8; 10];
+    file.read_exact(&mut id3).ok()?;
+    let start = match &id3[..3] {
+        b"ID3" => 10 + id3v2_size(&id3),
+        _ => 0,
+    };
+    file.seek(SeekFrom::Start(start)).ok()?;
+
+    let mut buf = Vec::with_capacity(MP3_SEARCH_LEN);
+    file.take(MP3_SEARCH_LEN as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+    mpeg_header_duration(&buf)
+}
+
+/// Size of an ID3v2 tag after its 10 byte header, including the optional footer.
+fn id3v2_size(header: &[u8; 10]) -> u64 {
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, byte| (size << 7) | u64::from(byte & 0x7f));
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    size + footer
+}
+
+/// MPEG audio frame header fields needed to interpret the Xing/VBRI tags.
+struct FrameHeader {
+    mpeg1: bool,
+    layer: u8,
+    sample_rate: u32,
+    mono: bool,
+    has_crc: bool,
+}
+
+impl FrameHeader {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let [b0, b1, b2, b3] = *bytes.get(..4)? else {
+            return None;
+        };
+        if b0 != 0xff || b1 & 0xe0 != 0xe0 {
+            return None;
+        }
+        let version = (b1 >> 3) & 0b11;
+        let layer = match (b1 >> 1) & 0b11 {
+            0b11 => 1,
+            0b10 => 2,
+            0b01 => 3,
+            _ => return None,
+        };
+        let bitrate_index = b2 >> 4;
+        if version == 0b01 || bitrate_index == 0 || bitrate_index == 0b1111 {
+            return None;
+        }
+        let base_rate = match (b2 >> 2) & 0b11 {
+            0 => 44100,
+            1 => 48000,
+            2 => 32000,
+            _ => return None,
+        };
+        let sample_rate = match version {
+            0b11 => base_rate,
+            0b10 => base_rate / 2,
+            _ => base_rate / 4,
+        };
+        Some(Self {
+            mpeg1: version == 0b11,
+            layer,
+            sample_rate,
+            mono: b3 >> 6 == 0b11,
+            has_crc: b1 & 1 == 0,
+        })
+    }
+
+    fn samples_per_frame(&self) -> u64 {
+        match (self.layer, self.mpeg1) {
+            (1, _) => 384,
+            (2, _) | (3, true) => 1152,
+            _ => 576,
+        }
+    }
+
+    /// Offset of the Xing/Info tag from the start of the frame: it follows the side information.
+    fn xing_offset(&self) -> usize {
+        let side_info = match (self.mpeg1, self.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        };
+        4 + usize::from(self.has_crc) * 2 + side_info
+    }
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Reads the frame count from a Xing/Info or VBRI tag in the first MPEG frame of `bytes`,
+/// subtracting the encoder delay and padding recorded in a LAME extension.
+fn mpeg_header_duration(bytes: &[u8]) -> Option<Duration> {
+    let start =
+        (0..bytes.len().saturating_sub(4)).find(|&i| FrameHeader::parse(&bytes[i..]).is_some())?;
+    let frame = &bytes[start..];
+    let header = FrameHeader::parse(frame)?;
+
+    let xing = header.xing_offset();
+    let (frames, trimmed) = match frame.get(xing..xing + 4) {
+        Some(b"Xing") | Some(b"Info") => {
+            let flags = be_u32(frame, xing + 4)?;
+            if flags & 0x1 == 0 {
+                return None;
+            }
+            let frames = u64::from(be_u32(frame, xing + 8)?);
+            // Optional fields before the LAME extension: frames, bytes, TOC, quality.
+            let lame = xing
+                + 8
+                + [(0x1, 4), (0x2, 4), (0x4, 100), (0x8, 4)]
+                    .iter()
+                    .filter(|(flag, _)| flags & flag != 0)
+                    .map(|(_, len)| len)
+                    .sum::<usize>();
+            let trimmed = match frame.get(lame..lame + 24) {
+                Some(ext) if ext.starts_with(b"LAME") || ext.starts_with(b"Lavc") => {
+                    let delay = (u64::from(ext[21]) << 4) | u64::from(ext[22] >> 4);
+                    let padding = (u64::from(ext[22] & 0x0f) << 8) | u64::from(ext[23]);
+                    delay + padding
+                }
+                _ => 0,
+            };
+            (frames, trimmed)
+        }
+        _ if frame.get(36..40) == Some(b"VBRI") => (u64::from(be_u32(frame, 36 + 14)?), 0),
+        _ => return None,
+    };
+    let samples = (frames * header.samples_per_frame()).saturating_sub(trimmed);
+    Some(Duration::from_secs_f64(
+        samples as f64 / f64::from(header.sample_rate),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// MPEG-1 Layer III, 128 kbit/s, 44.1 kHz, joint stereo, no CRC.
+    const HEADER: [u8; 4] = [0xff, 0xfb, 0x90, 0x64];
+
+    fn frame_with(tag_at: usize, tag: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&HEADER);
+        frame[tag_at..tag_at + tag.len()].copy_from_slice(tag);
+        frame
+    }
+
+    #[test]
+    fn test_xing_frame_count() {
+        let mut tag = b"Xing".to_vec();
+        tag.extend(1u32.to_be_bytes());
+        tag.extend(1000u32.to_be_bytes());
+        let mut bytes = vec![0u8; 10]; // junk before the first frame
+        bytes.extend(frame_with(36, &tag));
+
+        let duration = mpeg_header_duration(&bytes).unwrap();
+        assert_eq!(duration.as_millis(), 1000 * 1152 * 1000 / 44100);
+    }
+
+    #[test]
+    fn test_lame_delay_and_padding() {
+        let mut tag = b"Info".to_vec();
+        tag.extend(1u32.to_be_bytes());
+        tag.extend(100u32.to_be_bytes());
+        let mut lame = b"LAME3.100".to_vec();
+        lame.resize(21, 0);
+        // delay 576, padding 1152 - 576 + 1 packed in 12 bit fields
+        let (delay, padding) = (576u32, 577u32);
+        lame.extend([
+            (delay >> 4) as u8,
+            (((delay & 0xf) << 4) | (padding >> 8)) as u8,
+            padding as u8,
+        ]);
+        tag.extend(lame);
+
+        let duration = mpeg_header_duration(&frame_with(36, &tag)).unwrap();
+        let samples = 100 * 1152 - 576 - 577;
+        assert_eq!(duration, Duration::from_secs_f64(samples as f64 / 44100.0));
+    }
+
+    #[test]
+    fn test_vbri_frame_count() {
+        let mut tag = b"VBRI".to_vec();
+        tag.extend([0u8; 10]);
+        tag.extend(441u32.to_be_bytes());
+        let duration = mpeg_header_duration(&frame_with(36, &tag)).unwrap();
+        assert_eq!(duration.as_secs(), 441 * 1152 / 44100);
+    }
+
+    #[test]
+    fn test_no_header() {
+        assert_eq!(mpeg_header_duration(&frame_with(36, b"none")), None);
+        assert_eq!(mpeg_header_duration(b"not an mp3 at all"), None);
+    }
+}
@@ -0,0 +1,132 @@
+//! Liked tracks ("Улюблені"), remembered by content so renaming or moving a file keeps them.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+
+use super::{ContentHash, Track};
+use crate::storage;
+
+pub const FILE_NAME: &str = "favorites.json";
+
+/// Current layout version of the favorites file.
+const VERSION: u64 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FavoritesFile {
+    version: u64,
+    tracks: Vec<Favorite>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Favorite {
+    hash: ContentHash,
+    /// Where the file was when it was last liked, to keep the file readable.
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Favorites {
+    liked: HashMap<ContentHash, PathBuf>,
+}
+
+impl Favorites {
+    /// Loads the favorites; a missing file means nothing is liked yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let Some(file) = storage::load_json::<FavoritesFile>(path)? else {
+            return Ok(Self::default());
+        };
+        if file.version != VERSION {
+            return Err(eyre!("unsupported favorites version {}", file.version));
+        }
+        let liked = file
+            .tracks
+            .into_iter()
+            .map(|favorite| (favorite.hash, favorite.path))
+            .collect();
+        Ok(Self { liked })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tracks: Vec<_> = self
+            .liked
+            .iter()
+            .map(|(&hash, path)| Favorite {
+                hash,
+                path: path.clone(),
+            })
+            .collect();
+        tracks.sort_by(|a, b| a.path.cmp(&b.path));
+        storage::save_json(
+            path,
+            &FavoritesFile {
+                version: VERSION,
+                tracks,
+            },
+        )
+    }
+
+    pub fn contains(&self, track: &Track) -> bool {
+        self.liked.contains_key(&track.content_hash)
+    }
+
+    /// Likes `track`, or unlikes it if it was liked. Returns whether it is liked now.
+    pub fn toggle(&mut self, track: &Track) -> bool {
+        if self.liked.remove(&track.content_hash).is_some() {
+            return false;
+        }
+        self.liked.insert(track.content_hash, track.path.clone());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn track(path: &str, hash: u64) -> Track {
+        Track {
+            content_hash: ContentHash(hash),
+            ..Track::new(PathBuf::from(path))
+        }
+    }
+
+    #[test]
+    fn test_favorites_survive_a_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let mut favorites = Favorites::default();
+        assert!(favorites.toggle(&track("/music/a.mp3", 1)));
+        assert!(favorites.toggle(&track("/music/b.mp3", 2)));
+        assert!(!favorites.toggle(&track("/music/b.mp3", 2)));
+        favorites.save(&path).unwrap();
+
+        let favorites = Favorites::load(&path).unwrap();
+        assert!(favorites.contains(&track("/music/renamed.mp3", 1)));
+        assert!(!favorites.contains(&track("/music/b.mp3", 2)));
+        assert!(!favorites.contains(&track("/music/a.mp3", 3)));
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        fs::write(&path, r#"{"version": 2, "tracks": []}"#).unwrap();
+        assert!(Favorites::load(&path).is_err());
+        assert_eq!(
+            Favorites::load(&dir.path().join("missing.json"))
+                .unwrap()
+                .liked
+                .len(),
+            0
+        );
+    }
+}
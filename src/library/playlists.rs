@@ -0,0 +1,418 @@
+//! Named playlists ("Списки відтворення") kept in the data directory.
+//!
+//! Entries remember a track by its [`ContentHash`] and its last known path, so a playlist
+//! survives files being renamed or moved within the library. Playlist files of other players are
+//! read and written by [`formats`].
+
+pub mod formats;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    Result,
+    eyre::{WrapErr, eyre},
+};
+use serde::{Deserialize, Serialize};
+
+use self::formats::{FileEntry, Format};
+use super::{ContentHash, Track};
+use crate::storage;
+
+pub const FILE_NAME: &str = "playlists.json";
+
+/// Current layout version of the playlists file.
+const VERSION: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistEntry {
+    /// Missing for imported entries whose file was not found.
+    pub hash: Option<ContentHash>,
+    pub path: PathBuf,
+}
+
+impl From<&Track> for PlaylistEntry {
+    fn from(track: &Track) -> Self {
+        Self {
+            hash: Some(track.content_hash),
+            path: track.path.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlaylistsFile {
+    version: u64,
+    playlists: Vec<Playlist>,
+}
+
+/// What [`Playlists::import`] made of a playlist file.
+#[derive(Debug, Clone)]
+pub struct ImportReport {
+    pub index: usize,
+    /// Entries no file was found for. They stay in the playlist under their best guess of a path
+    /// and turn up once the file appears in the library.
+    pub unresolved: Vec<FileEntry>,
+}
+
+/// All playlists, in the order they were created.
+#[derive(Debug, Clone, Default)]
+pub struct Playlists {
+    playlists: Vec<Playlist>,
+}
+
+impl Playlists {
+    /// Loads the playlists; a missing file means there are none yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let Some(file) = storage::load_json::<PlaylistsFile>(path)? else {
+            return Ok(Self::default());
+        };
+        if file.version != VERSION {
+            return Err(eyre!("unsupported playlists version {}", file.version));
+        }
+        Ok(Self {
+            playlists: file.playlists,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        storage::save_json(
+            path,
+            &PlaylistsFile {
+                version: VERSION,
+                playlists: self.playlists.clone(),
+            },
+        )
+    }
+
+    pub fn playlists(&self) -> &[Playlist] {
+        &self.playlists
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.playlists
+            .iter()
+            .position(|playlist| playlist.name == name)
+    }
+
+    /// Adds an empty playlist and returns its index. Names must be unique and not blank.
+    pub fn create(&mut self, name: &str) -> Result<usize> {
+        let name = self.check_name(name, None)?;
+        self.playlists.push(Playlist {
+            name,
+            entries: Vec::new(),
+        });
+        Ok(self.playlists.len() - 1)
+    }
+
+    pub fn rename(&mut self, index: usize, name: &str) -> Result<()> {
+        let name = self.check_name(name, Some(index))?;
+        let playlist = self
+            .playlists
+            .get_mut(index)
+            .ok_or_else(|| eyre!("no playlist {index}"))?;
+        playlist.name = name;
+        Ok(())
+    }
+
+    pub fn delete(&mut self, index: usize) -> Option<Playlist> {
+        (index < self.playlists.len()).then(|| self.playlists.remove(index))
+    }
+
+    /// Appends tracks to the end of a playlist.
+    pub fn add<'a>(&mut self, index: usize, tracks: impl IntoIterator<Item = &'a Track>) {
+        if let Some(playlist) = self.playlists.get_mut(index) {
+            playlist
+                .entries
+                .extend(tracks.into_iter().map(PlaylistEntry::from));
+        }
+    }
+
+    pub fn remove_entry(&mut self, index: usize, entry: usize) -> Option<PlaylistEntry> {
+        let entries = &mut self.playlists.get_mut(index)?.entries;
+        (entry < entries.len()).then(|| entries.remove(entry))
+    }
+
+    /// Swaps an entry with its neighbour above (`up`) or below. Returns its new position.
+    pub fn move_entry(&mut self, index: usize, entry: usize, up: bool) -> Option<usize> {
+        let entries = &mut self.playlists.get_mut(index)?.entries;
+        let other = if up { entry.checked_sub(1)? } else { entry + 1 };
+        if entry >= entries.len() || other >= entries.len() {
+            return None;
+        }
+        entries.swap(entry, other);
+        Some(other)
+    }
+
+    /// The tracks of a playlist, one per entry, looked up in `library` by path and, for moved or
+    /// renamed files, by content. Entries whose file is not in the library (yet) get a bare track
+    /// for their last known path.
+    pub fn resolve(&self, index: usize, library: &[Track]) -> Vec<Track> {
+        let Some(playlist) = self.playlists.get(index) else {
+            return Vec::new();
+        };
+        let by_path: HashMap<_, _> = library
+            .iter()
+            .map(|track| (track.path.as_path(), track))
+            .collect();
+        let by_hash: HashMap<_, _> = library
+            .iter()
+            .map(|track| (track.content_hash, track))
+            .collect();
+        playlist
+            .entries
+            .iter()
+            .map(|entry| {
+                let track = by_path
+                    .get(entry.path.as_path())
+                    .filter(|track| entry.hash.is_none_or(|hash| hash == track.content_hash))
+                    .or_else(|| by_hash.get(&entry.hash?));
+                match track {
+                    Some(&track) => track.clone(),
+                    None => Track {
+                        content_hash: entry.hash.unwrap_or_default(),
+                        ..Track::new(entry.path.clone())
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Reads an M3U/M3U8, PLS or XSPF file into a new playlist named after the file. Entries are
+    /// looked up next to the file and under the library `roots`; see [`formats::candidates`].
+    pub fn import(
+        &mut self,
+        file: &Path,
+        roots: &[PathBuf],
+        library: &[Track],
+    ) -> Result<ImportReport> {
+        let format = Format::of(file)?;
+        let bytes =
+            fs::read(file).wrap_err_with(|| format!("Не вдалося прочитати {}", file.display()))?;
+        let file_entries = formats::parse(format, &String::from_utf8_lossy(&bytes))
+            .wrap_err_with(|| format!("Не вдалося розібрати {}", file.display()))?;
+
+        let by_path: HashMap<_, _> = library
+            .iter()
+            .map(|track| (track.path.as_path(), track))
+            .collect();
+        let mut unresolved = Vec::new();
+        let entries = file_entries
+            .into_iter()
+            .map(|file_entry| {
+                let candidates = formats::candidates(format, &file_entry.location, file, roots);
+                let found = candidates
+                    .iter()
+                    .find_map(|path| match by_path.get(path.as_path()) {
+                        Some(&track) => Some(PlaylistEntry::from(track)),
+                        None => path.is_file().then(|| PlaylistEntry {
+                            hash: ContentHash::of(path).ok(),
+                            path: path.clone(),
+                        }),
+                    });
+                found.unwrap_or_else(|| {
+                    let path = candidates
+                        .into_iter()
+                        .next()
+                        .unwrap_or_else(|| PathBuf::from(&file_entry.location));
+                    unresolved.push(file_entry);
+                    PlaylistEntry { hash: None, path }
+                })
+            })
+            .collect();
+
+        let stem = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let index = self.create(&self.unique_name(&stem))?;
+        self.playlists[index].entries = entries;
+        Ok(ImportReport { index, unresolved })
+    }
+
+    /// Writes a playlist to an M3U/M3U8, PLS or XSPF file, picked by its extension.
+    pub fn export(&self, index: usize, file: &Path, library: &[Track]) -> Result<()> {
+        let format = Format::of(file)?;
+        let playlist = self
+            .playlists
+            .get(index)
+            .ok_or_else(|| eyre!("no playlist {index}"))?;
+        let dir = file.parent().unwrap_or(Path::new(""));
+        let text = formats::write(format, &playlist.name, &self.resolve(index, library), dir);
+        fs::write(file, text).wrap_err_with(|| format!("Не вдалося записати {}", file.display()))
+    }
+
+    /// `name`, or `name (2)`, `name (3)`, ... if a playlist is already called that.
+    fn unique_name(&self, name: &str) -> String {
+        let name = name.trim();
+        (1..)
+            .map(|n| match n {
+                1 => name.to_string(),
+                n => format!("{name} ({n})"),
+            })
+            .find(|candidate| self.position(candidate).is_none())
+            .unwrap_or_default()
+    }
+
+    fn check_name(&self, name: &str, renaming: Option<usize>) -> Result<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(eyre!("Назва не може бути порожньою"));
+        }
+        match self.position(name) {
+            Some(existing) if Some(existing) != renaming => Err(eyre!("Список «{name}» уже існує")),
+            _ => Ok(name.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn track(path: &str, hash: u64) -> Track {
+        Track {
+            content_hash: ContentHash(hash),
+            ..Track::new(PathBuf::from(path))
+        }
+    }
+
+    fn names(playlists: &Playlists) -> Vec<&str> {
+        playlists
+            .playlists()
+            .iter()
+            .map(|playlist| playlist.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_create_rename_delete() {
+        let mut playlists = Playlists::default();
+        assert_eq!(playlists.create(" Дорога ").unwrap(), 0);
+        assert_eq!(playlists.create("Спорт").unwrap(), 1);
+        assert!(playlists.create("Дорога").is_err());
+        assert!(playlists.create("  ").is_err());
+
+        assert!(playlists.rename(1, "Дорога").is_err());
+        playlists.rename(1, "Біг").unwrap();
+        // Keeping the same name is not a clash with itself
+        playlists.rename(0, "Дорога").unwrap();
+        assert_eq!(names(&playlists), vec!["Дорога", "Біг"]);
+
+        assert_eq!(playlists.delete(0).unwrap().name, "Дорога");
+        assert_eq!(playlists.delete(5), None);
+        assert_eq!(names(&playlists), vec!["Біг"]);
+    }
+
+    #[test]
+    fn test_entries_reorder_and_resolve_after_a_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let (a, b, c) = (track("/a.mp3", 1), track("/b.mp3", 2), track("/c.mp3", 3));
+        let mut playlists = Playlists::default();
+        playlists.create("Мікс").unwrap();
+        playlists.add(0, [&a, &b, &c]);
+        assert_eq!(playlists.move_entry(0, 2, true), Some(1));
+        assert_eq!(playlists.move_entry(0, 0, true), None);
+        assert_eq!(
+            playlists.remove_entry(0, 0).unwrap().hash,
+            Some(a.content_hash)
+        );
+        playlists.save(&path).unwrap();
+
+        let playlists = Playlists::load(&path).unwrap();
+        let library = vec![track("/moved/b.mp3", 2)];
+        let titles: Vec<_> = playlists
+            .resolve(0, &library)
+            .iter()
+            .map(|track| track.path.clone())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![PathBuf::from("/c.mp3"), PathBuf::from("/moved/b.mp3")]
+        );
+        assert!(playlists.resolve(7, &library).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_keeps_copies_with_the_same_content_apart() {
+        let (a, copy) = (track("/a.mp3", 1), track("/copy of a.mp3", 1));
+        let mut playlists = Playlists::default();
+        playlists.create("Мікс").unwrap();
+        playlists.add(0, [&copy, &a]);
+
+        let library = vec![a.clone(), copy.clone()];
+        assert_eq!(playlists.resolve(0, &library), vec![copy, a]);
+    }
+
+    #[test]
+    fn test_import_resolves_entries_and_reports_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("music");
+        fs::create_dir_all(root.join("A")).unwrap();
+        let known = track(root.join("A/known.mp3").to_str().unwrap(), 7);
+        let loose = dir.path().join("loose.mp3");
+        fs::write(&loose, b"not in the library").unwrap();
+        let file = dir.path().join("Дорога.m3u8");
+        fs::write(
+            &file,
+            "#EXTM3U\n/volume1/music/A/known.mp3\nloose.mp3\n#EXTINF:1,Гурт - Нема\nA/gone.mp3\n",
+        )
+        .unwrap();
+
+        let mut playlists = Playlists::default();
+        playlists.create("Дорога").unwrap();
+        let report = playlists
+            .import(
+                &file,
+                std::slice::from_ref(&root),
+                std::slice::from_ref(&known),
+            )
+            .unwrap();
+        assert_eq!(report.index, 1);
+        assert_eq!(
+            report.unresolved,
+            vec![FileEntry {
+                location: "A/gone.mp3".to_string(),
+                title: Some("Гурт - Нема".to_string()),
+            }]
+        );
+        let playlist = &playlists.playlists()[1];
+        assert_eq!(playlist.name, "Дорога (2)");
+        assert_eq!(
+            playlist.entries,
+            vec![
+                PlaylistEntry::from(&known),
+                PlaylistEntry {
+                    hash: Some(ContentHash::of(&loose).unwrap()),
+                    path: loose,
+                },
+                PlaylistEntry {
+                    hash: None,
+                    path: dir.path().join("A/gone.mp3"),
+                },
+            ]
+        );
+
+        let exported = dir.path().join("out.pls");
+        let library = [known];
+        playlists.export(1, &exported, &library).unwrap();
+        let report = playlists.import(&exported, &[], &library).unwrap();
+        assert_eq!(report.unresolved.len(), 1);
+        assert_eq!(
+            playlists.playlists()[2].entries,
+            playlists.playlists()[1].entries
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Playlist files shared with other players: M3U/M3U8 (with `#EXTINF`), PLS and XSPF.
+//!
+//! Parsing only extracts where each entry points; matching entries against the library is up to
+//! [`Playlists::import`](super::Playlists::import).
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    path::{Component, Path, PathBuf},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::library::Track;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `.m3u` and `.m3u8`; both are read and written as UTF-8.
+    M3u,
+    Pls,
+    Xspf,
+}
+
+impl Format {
+    /// Picks the format by the file extension.
+    pub fn of(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("m3u" | "m3u8") => Ok(Self::M3u),
+            Some("pls") => Ok(Self::Pls),
+            Some("xspf") => Ok(Self::Xspf),
+            _ => Err(eyre!(
+                "Невідомий формат списку: {}; підтримуються .m3u, .m3u8, .pls і .xspf",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// An entry as written in a playlist file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// A path, absolute or relative to the playlist file, or a URL.
+    pub location: String,
+    /// "Artist - Title" when the file says so, to tell unresolved entries apart.
+    pub title: Option<String>,
+}
+
+pub fn parse(format: Format, text: &str) -> Result<Vec<FileEntry>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    match format {
+        Format::M3u => Ok(parse_m3u(text)),
+        Format::Pls => Ok(parse_pls(text)),
+        Format::Xspf => parse_xspf(text),
+    }
+}
+
+fn parse_m3u(text: &str) -> Vec<FileEntry> {
+    let mut entries = Vec::new();
+    let mut title = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<секунди> [атрибути],<Виконавець - Назва>
+            title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string())
+                .filter(|title| !title.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push(FileEntry {
+                location: line.to_string(),
+                title: title.take(),
+            });
+        }
+    }
+    entries
+}
+
+fn parse_pls(text: &str) -> Vec<FileEntry> {
+    // FileN / TitleN можуть іти в будь-якому порядку, тож збираємо їх за номером
+    let mut numbered: BTreeMap<u32, (Option<String>, Option<String>)> = BTreeMap::new();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let (field, number) = key.split_at(key.find(|c: char| c.is_ascii_digit()).unwrap_or(0));
+        let Ok(number) = number.parse() else {
+            continue;
+        };
+        let (location, title) = numbered.entry(number).or_default();
+        match field {
+            "file" => *location = Some(value.trim().to_string()),
+            "title" => *title = Some(value.trim().to_string()).filter(|title| !title.is_empty()),
+            _ => {}
+        }
+    }
+    numbered
+        .into_values()
+        .filter_map(|(location, title)| {
+            Some(FileEntry {
+                location: location?,
+                title,
+            })
+        })
+        .collect()
+}
+
+fn parse_xspf(text: &str) -> Result<Vec<FileEntry>> {
+    let document = roxmltree::Document::parse(text)?;
+    let entries = document
+        .descendants()
+        .filter(|node| node.has_tag_name("track"))
+        .filter_map(|track| {
+            let child = |name: &str| {
+                track
+                    .children()
+                    .find(|node| node.has_tag_name(name))
+                    .and_then(|node| node.text())
+                    .map(str::trim)
+                    .filter(|text| !text.is_empty())
+            };
+            let title = match (child("creator"), child("title")) {
+                (Some(creator), Some(title)) => Some(format!("{creator} - {title}")),
+                (None, title) => title.map(str::to_string),
+                (Some(_), None) => None,
+            };
+            Some(FileEntry {
+                location: child("location")?.to_string(),
+                title,
+            })
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Where on disk an entry may be, most likely first: the location itself, taken relative to the
+/// folder of the playlist file, then every tail of it under each library root. The tails catch
+/// paths written on another machine, such as `/volume1/music/...` on the NAS or `D:\Music\...`.
+/// Non-file URLs (streams) have no candidates.
+pub fn candidates(
+    format: Format,
+    location: &str,
+    playlist: &Path,
+    roots: &[PathBuf],
+) -> Vec<PathBuf> {
+    let Some(path) = location_path(format, location) else {
+        return Vec::new();
+    };
+    let dir = playlist.parent().unwrap_or(Path::new(""));
+    let parts: Vec<_> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+    let tails = roots.iter().flat_map(|root| {
+        (0..parts.len()).map(|skip| {
+            parts[skip..]
+                .iter()
+                .fold(root.clone(), |path, part| path.join(part))
+        })
+    });
+    std::iter::once(normalize(&dir.join(&path)))
+        .chain(tails)
+        .collect()
+}
+
+fn location_path(format: Format, location: &str) -> Option<PathBuf> {
+    // Буква диска на кшталт `C:\` теж розбирається як схема URL
+    if let Ok(url) = Url::parse(location)
+        && url.scheme().len() > 1
+    {
+        return (url.scheme() == "file")
+            .then(|| url.to_file_path().ok())
+            .flatten();
+    }
+    let location = match format {
+        // У XSPF це відносне посилання, тож пробіли й кирилиця закодовані
+        Format::Xspf => percent_decode_str(location)
+            .decode_utf8_lossy()
+            .into_owned(),
+        // Списки з Windows пишуть шляхи через зворотну скісну риску
+        Format::M3u | Format::Pls => location.replace('\\', "/"),
+    };
+    Some(PathBuf::from(location))
+}
+
+/// Resolves `.` and `..` without touching the disk, so paths compare equal to the library's.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+/// Renders a playlist file. Tracks inside `dir`, the folder the file goes to, get paths relative
+/// to it, so the playlist keeps working when that folder is mounted elsewhere.
+pub fn write(format: Format, name: &str, tracks: &[Track], dir: &Path) -> String {
+    let mut out = String::new();
+    match format {
+        Format::M3u => {
+            out.push_str("#EXTM3U\n");
+            let _ = writeln!(out, "#PLAYLIST:{name}");
+            for track in tracks {
+                let _ = writeln!(out, "#EXTINF:{},{}", seconds(track), display_title(track));
+                let _ = writeln!(out, "{}", relative(&track.path, dir).display());
+            }
+        }
+        Format::Pls => {
+            out.push_str("[playlist]\n");
+            for (number, track) in (1..).zip(tracks) {
+                let _ = writeln!(out, "File{number}={}", relative(&track.path, dir).display());
+                let _ = writeln!(out, "Title{number}={}", display_title(track));
+                let _ = writeln!(out, "Length{number}={}", seconds(track));
+            }
+            let _ = writeln!(out, "NumberOfEntries={}", tracks.len());
+            out.push_str("Version=2\n");
+        }
+        Format::Xspf => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+            let _ = writeln!(out, "  <title>{}</title>", escape(name));
+            out.push_str("  <trackList>\n");
+            for track in tracks {
+                out.push_str("    <track>\n");
+                let _ = writeln!(
+                    out,
+                    "      <location>{}</location>",
+                    escape(&url(&track.path, dir))
+                );
+                if let Some(artist) = track.artist() {
+                    let _ = writeln!(out, "      <creator>{}</creator>", escape(artist));
+                }
+                let _ = writeln!(out, "      <title>{}</title>", escape(&track.title()));
+                if !track.duration.is_zero() {
+                    let _ = writeln!(
+                        out,
+                        "      <duration>{}</duration>",
+                        track.duration.as_millis()
+                    );
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+        }
+    }
+    out
+}
+
+fn display_title(track: &Track) -> String {
+    match track.artist() {
+        Some(artist) => format!("{artist} - {}", track.title()),
+        None => track.title(),
+    }
+}
+
+/// Whole seconds, or -1 when the duration is unknown, as `#EXTINF` and PLS expect.
+fn seconds(track: &Track) -> i64 {
+    match track.duration.as_secs() {
+        0 => -1,
+        seconds => seconds.try_into().unwrap_or(i64::MAX),
+    }
+}
+
+fn relative<'a>(path: &'a Path, dir: &Path) -> &'a Path {
+    path.strip_prefix(dir).unwrap_or(path)
+}
+
+fn url(path: &Path, dir: &Path) -> String {
+    let Ok(url) = Url::from_file_path(path) else {
+        return path.display().to_string();
+    };
+    match Url::from_directory_path(dir) {
+        Ok(base) if path.starts_with(dir) => base.make_relative(&url),
+        _ => None,
+    }
+    .unwrap_or_else(|| url.into())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn entry(location: &str, title: Option<&str>) -> FileEntry {
+        FileEntry {
+            location: location.to_string(),
+            title: title.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_each_format() {
+        let m3u = "\u{feff}#EXTM3U\n#EXTINF:245 tvg-id=\"x\",Гурт - Пісня\r\nA/пісня.mp3\n\n# коментар\nhttp://radio/stream\n";
+        assert_eq!(
+            parse(Format::M3u, m3u).unwrap(),
+            vec![
+                entry("A/пісня.mp3", Some("Гурт - Пісня")),
+                entry("http://radio/stream", None)
+            ]
+        );
+
+        let pls = "[playlist]\nTitle2=Друга\nFile2=b.flac\nFile1=a.mp3\nLength1=10\nNumberOfEntries=2\nVersion=2\n";
+        assert_eq!(
+            parse(Format::Pls, pls).unwrap(),
+            vec![entry("a.mp3", None), entry("b.flac", Some("Друга"))]
+        );
+
+        let xspf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <trackList>
+                <track><location>A/%D0%BF%D1%96%D1%81%D0%BD%D1%8F.mp3</location><creator>Гурт</creator><title>Пісня</title></track>
+                <track><title>Без адреси</title></track>
+              </trackList>
+            </playlist>"#;
+        assert_eq!(
+            parse(Format::Xspf, xspf).unwrap(),
+            vec![entry(
+                "A/%D0%BF%D1%96%D1%81%D0%BD%D1%8F.mp3",
+                Some("Гурт - Пісня")
+            )]
+        );
+        assert!(parse(Format::Xspf, "<playlist>").is_err());
+    }
+
+    #[test]
+    fn test_candidates_try_the_playlist_folder_then_the_roots() {
+        let playlist = Path::new("/nas/lists/mix.m3u8");
+        let roots = [PathBuf::from("/music")];
+        assert_eq!(
+            candidates(Format::M3u, "../A/song.mp3", playlist, &roots),
+            vec![
+                PathBuf::from("/nas/A/song.mp3"),
+                PathBuf::from("/music/A/song.mp3"),
+                PathBuf::from("/music/song.mp3"),
+            ]
+        );
+        assert_eq!(
+            candidates(Format::M3u, r"D:\Music\A\song.mp3", playlist, &roots)[3],
+            PathBuf::from("/music/A/song.mp3")
+        );
+        assert_eq!(
+            candidates(Format::Xspf, "file:///music/A/my%20song.mp3", playlist, &[])[0],
+            PathBuf::from("/music/A/my song.mp3")
+        );
+        assert_eq!(
+            candidates(Format::Xspf, "A/my%20song.mp3", playlist, &[])[0],
+            PathBuf::from("/nas/lists/A/my song.mp3")
+        );
+        assert!(candidates(Format::M3u, "https://radio/stream", playlist, &roots).is_empty());
+    }
+
+    #[test]
+    fn test_written_files_parse_back() {
+        let dir = Path::new("/music/lists");
+        let mut inside = Track::new(PathBuf::from("/music/lists/A/my song.mp3"));
+        inside.tags.artist = Some("Гурт & Ко".to_string());
+        inside.duration = Duration::from_secs(245);
+        let outside = Track::new(PathBuf::from("/other/b.flac"));
+        let tracks = [inside, outside];
+
+        for format in [Format::M3u, Format::Pls, Format::Xspf] {
+            let text = write(format, "Мікс", &tracks, dir);
+            let entries = parse(format, &text).unwrap();
+            let paths: Vec<_> = entries
+                .iter()
+                .map(|entry| candidates(format, &entry.location, &dir.join("mix"), &[])[0].clone())
+                .collect();
+            assert_eq!(
+                paths,
+                vec![tracks[0].path.clone(), tracks[1].path.clone()],
+                "{format:?}"
+            );
+            assert_eq!(
+                entries[0].title.as_deref(),
+                Some("Гурт & Ко - my song"),
+                "{format:?}"
+            );
+        }
+    }
+}
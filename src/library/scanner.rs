@@ -0,0 +1,388 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::{
+    errors::Error as SymphoniaError, formats::FormatOptions, io::MediaSourceStream,
+    meta::MetadataOptions, probe::Hint,
+};
+use symphonia::default::get_probe;
+
+use super::{
+    ContentHash, Track,
+    cache::{FileStamp, LibraryCache},
+    duration, tags,
+};
+use crate::audio::codecs;
+
+/// Containers and codecs compiled into the decoder, as shown by `--version`.
+pub const SUPPORTED_FORMATS: &[&str] = &[
+    "MP3",
+    "AAC (ADTS)",
+    "MP4/M4A (AAC, ALAC)",
+    "FLAC",
+    "Ogg (Vorbis, Opus, FLAC)",
+    "WAV",
+    "AIFF",
+];
+
+/// Files are recognised by their content, not their name. These extensions only decide whether a
+/// file the decoder does not recognise is reported as broken or quietly skipped as not audio.
+const AUDIO_EXTENSIONS: [&str; 14] = [
+    "mp3", "mp2", "mp1", "flac", "wav", "aif", "aiff", "aac", "m4a", "mp4", "alac", "ogg", "oga",
+    "opus",
+];
+
+/// A file or directory the scanner could not read, with a human readable reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Everything that went wrong during a scan. A non-empty report does not mean the scan failed:
+/// every readable track is still returned.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub errors: Vec<ScanError>,
+}
+
+impl ScanReport {
+    pub fn push(&mut self, path: &Path, reason: impl ToString) {
+        self.errors.push(ScanError {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// Progress of a running scan, reported as each file is probed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanEvent {
+    Track(Track),
+    Progress { scanned: usize, total: usize },
+    Done(ScanReport),
+}
+
+/// Scans every root recursively and probes each audio file found, reporting every track as soon
+/// as it is read. Files whose size and modification time match `cache` are taken from it
+/// instead of being decoded again.
+///
+/// Returns the cache rebuilt from this scan, or `None` if `on_event` stopped the scan early with
+/// [`ControlFlow::Break`].
+pub fn scan(
+    roots: &[PathBuf],
+    cache: &LibraryCache,
+    mut on_event: impl FnMut(ScanEvent) -> ControlFlow<()>,
+) -> Option<LibraryCache> {
+    let mut report = ScanReport::default();
+    let mut fresh = LibraryCache::default();
+    let files = walk(roots, &mut report);
+    let total = files.len();
+    for (scanned, path) in files.into_iter().enumerate() {
+        let flow = match read_track(&path, cache) {
+            Ok((stamp, Some(track))) => {
+                fresh.insert(stamp, track.clone());
+                on_event(ScanEvent::Track(track))
+            }
+            Ok((stamp, None)) => {
+                fresh.ignore(path, stamp);
+                ControlFlow::Continue(())
+            }
+            Err(err) => {
+                report.push(&path, err);
+                ControlFlow::Continue(())
+            }
+        };
+        let progress = ScanEvent::Progress {
+            scanned: scanned + 1,
+            total,
+        };
+        if flow.is_break() || on_event(progress).is_break() {
+            return None;
+        }
+    }
+    let _ = on_event(ScanEvent::Done(report));
+    Some(fresh)
+}
+
+/// Reads `path` from `cache` or probes it. Yields no track if the file is not audio.
+pub(super) fn read_track(
+    path: &Path,
+    cache: &LibraryCache,
+) -> io::Result<(FileStamp, Option<Track>)> {
+    let stamp = FileStamp::of(path)?;
+    if cache.is_ignored(path, stamp) {
+        return Ok((stamp, None));
+    }
+    if let Some(track) = cache.get(path, stamp) {
+        return Ok((stamp, Some(track.clone())));
+    }
+    match probe(path) {
+        Ok(track) => Ok((stamp, Some(track))),
+        Err(err) if err.kind() == io::ErrorKind::Unsupported && !has_audio_extension(path) => {
+            Ok((stamp, None))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Collects the files under `roots`, descending into nested folders. Directories are
+/// tracked by their canonical path, so symlink loops and roots nested in each other are only
+/// visited once.
+pub fn walk(roots: &[PathBuf], report: &mut ScanReport) -> Vec<PathBuf> {
+    let mut visited = HashSet::new();
+    let mut files = Vec::new();
+    for root in roots {
+        walk_dir(root, &mut visited, &mut files, report);
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn walk_dir(
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+    report: &mut ScanReport,
+) {
+    let canonical = match fs::canonicalize(dir) {
+        Ok(path) => path,
+        Err(err) => return report.push(dir, err),
+    };
+    if !visited.insert(canonical.clone()) {
+        return;
+    }
+    let entries = match fs::read_dir(&canonical) {
+        Ok(entries) => entries,
+        Err(err) => return report.push(dir, err),
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                report.push(dir, err);
+                continue;
+            }
+        };
+        // Hidden files include the `._Song.mp3` resource forks macOS leaves on shared drives.
+        if is_hidden(&path) {
+            continue;
+        }
+        // `metadata` follows symlinks, so linked folders are scanned as well.
+        match fs::metadata(&path) {
+            Ok(meta) if meta.is_dir() => walk_dir(&path, visited, files, report),
+            Ok(meta) if meta.is_file() => match fs::canonicalize(&path) {
+                Ok(path) => files.push(path),
+                Err(err) => report.push(&path, err),
+            },
+            Ok(_) => {}
+            Err(err) => report.push(&path, err),
+        }
+    }
+}
+
+fn has_audio_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+pub(super) fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Reads the tags and duration of a single audio file. Fails with [`io::ErrorKind::Unsupported`]
+/// if the content is not in a container the decoder knows.
+pub fn probe(path: &Path) -> io::Result<Track> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let file = fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    let size = metadata.len();
+    let added = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    hint.with_extension(&ext);
+
+    let mut probed = get_probe()
+        .format(
+            &hint,
+            mss,
+            // Gapless, so the duration leaves out encoder delay and padding
+            &FormatOptions {
+                enable_gapless: true,
+                ..Default::default()
+            },
+            &MetadataOptions::default(),
+        )
+        .map_err(|err| match err {
+            SymphoniaError::Unsupported(_) => {
+                io::Error::new(io::ErrorKind::Unsupported, "Not a known audio format")
+            }
+            err => io::Error::other(format!("Failed to parse audio: {err}")),
+        })?;
+
+    let track = probed
+        .format
+        .default_track()
+        .ok_or_else(|| io::Error::other("No default track found"))?;
+    let codec = track.codec_params.codec;
+    let sample_rate = track.codec_params.sample_rate;
+
+    // The container may be known while the codec is not.
+    let format = match codecs::registry().get_codec(codec) {
+        Some(codec) if codec.short_name.starts_with("pcm") => "PCM".to_string(),
+        Some(codec) => codec.short_name.to_uppercase(),
+        None => return Err(io::Error::other(format!("No decoder for codec {codec}"))),
+    };
+
+    let tags = tags::read_tags(&mut probed, path);
+    let duration = duration::measure(path, probed.format.as_mut());
+    let bitrate = (!duration.is_zero())
+        .then(|| (size as f64 * 8.0 / duration.as_secs_f64() / 1000.0).round() as u32);
+
+    Ok(Track {
+        content_hash: ContentHash::of(path)?,
+        tags,
+        duration,
+        format,
+        bitrate,
+        sample_rate,
+        added,
+        ..Track::new(path.to_path_buf())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_util::write_wav;
+
+    fn scan_all(roots: &[PathBuf]) -> (Vec<Track>, ScanReport) {
+        let (tracks, report, _) = scan_with_cache(roots);
+        (tracks, report)
+    }
+
+    fn scan_with_cache(roots: &[PathBuf]) -> (Vec<Track>, ScanReport, LibraryCache) {
+        let mut tracks = Vec::new();
+        let mut report = ScanReport::default();
+        let fresh = scan(roots, &LibraryCache::default(), |event| {
+            match event {
+                ScanEvent::Track(track) => tracks.push(track),
+                ScanEvent::Done(done) => report = done,
+                ScanEvent::Progress { .. } => {}
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        (tracks, report, fresh)
+    }
+
+    #[test]
+    fn test_scan_nested_folders() {
+        let root = tempfile::tempdir().unwrap();
+        let album = root.path().join("Artist").join("Album");
+        fs::create_dir_all(&album).unwrap();
+        write_wav(&root.path().join("single.wav"), [0; 16000]);
+        write_wav(&album.join("01 - Intro.wav"), [0; 16000]);
+        fs::write(album.join("cover.jpg"), b"not audio").unwrap();
+
+        let (tracks, report) = scan_all(&[root.path().to_path_buf()]);
+        assert!(report.errors.is_empty());
+        let mut names: Vec<_> = tracks.iter().map(|t| t.title()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Intro", "single"]);
+        assert!(
+            tracks
+                .iter()
+                .all(|t| t.duration.as_secs() == 2 && t.path.is_absolute())
+        );
+    }
+
+    #[test]
+    fn test_scan_reports_broken_files() {
+        let root = tempfile::tempdir().unwrap();
+        write_wav(&root.path().join("good.wav"), [0; 16000]);
+        fs::write(root.path().join("broken.mp3"), b"definitely not an mp3").unwrap();
+
+        let (tracks, report) = scan_all(&[root.path().to_path_buf()]);
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].path.ends_with("broken.mp3"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_symlink_loops() {
+        let root = tempfile::tempdir().unwrap();
+        let nested = root.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        write_wav(&nested.join("song.wav"), [0; 16000]);
+        std::os::unix::fs::symlink(root.path(), nested.join("loop")).unwrap();
+
+        let (tracks, report) = scan_all(&[root.path().to_path_buf(), nested.clone()]);
+        assert!(report.errors.is_empty());
+        assert_eq!(tracks.len(), 1);
+    }
+
+    #[test]
+    fn test_scan_reuses_cached_tracks() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("song.wav");
+        write_wav(&path, [0; 16000]);
+        let path = fs::canonicalize(path).unwrap();
+
+        let mut cached = Track::new(path.clone());
+        cached.tags.title = Some("From cache".into());
+        let mut cache = LibraryCache::default();
+        cache.insert(FileStamp::of(&path).unwrap(), cached);
+
+        let mut titles = Vec::new();
+        scan(&[root.path().to_path_buf()], &cache, |event| {
+            if let ScanEvent::Track(track) = event {
+                titles.push(track.title());
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(titles, vec!["From cache"]);
+    }
+
+    #[test]
+    fn test_scan_detects_audio_by_content() {
+        let root = tempfile::tempdir().unwrap();
+        write_wav(&root.path().join("Artist - Renamed.bin"), [0; 16000]);
+        write_wav(&root.path().join("._Artist - Renamed.wav"), [0; 16000]);
+        fs::write(root.path().join("notes.txt"), b"liner notes").unwrap();
+
+        let (tracks, report, fresh) = scan_with_cache(&[root.path().to_path_buf()]);
+        assert!(report.errors.is_empty());
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].title(), "Renamed");
+
+        let notes = fs::canonicalize(root.path().join("notes.txt")).unwrap();
+        assert!(fresh.is_ignored(&notes, FileStamp::of(&notes).unwrap()));
+    }
+}
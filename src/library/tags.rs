@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use symphonia::core::{
+    meta::{MetadataRevision, StandardTagKey, Value},
+    probe::ProbeResult,
+};
+
+use super::Tags;
+
+/// Collects the tags of a probed file. Tags stored in the container (Vorbis comments, MP4
+/// atoms, FLAC blocks) take precedence over tags found while probing (ID3v2); anything still
+/// missing is guessed from the file name.
+pub fn read_tags(probed: &mut ProbeResult, path: &Path) -> Tags {
+    let mut tags = Tags::default();
+    if let Some(revision) = probed.format.metadata().current() {
+        merge_revision(&mut tags, revision);
+    }
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        merge_revision(&mut tags, revision);
+    }
+    if let Some(stem) = path.file_stem() {
+        merge_missing(&mut tags, parse_file_name(&stem.to_string_lossy()));
+    }
+    tags
+}
+
+fn merge_revision(tags: &mut Tags, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let Some(key) = tag.std_key else {
+            continue;
+        };
+        match key {
+            StandardTagKey::TrackTitle => set_text(&mut tags.title, &tag.value),
+            StandardTagKey::Artist => set_text(&mut tags.artist, &tag.value),
+            StandardTagKey::AlbumArtist if tags.artist.is_none() => {
+                set_text(&mut tags.artist, &tag.value)
+            }
+            StandardTagKey::Album => set_text(&mut tags.album, &tag.value),
+            StandardTagKey::Genre => set_text(&mut tags.genre, &tag.value),
+            StandardTagKey::TrackNumber if tags.track_number.is_none() => {
+                tags.track_number = leading_number(&tag.value)
+            }
+            StandardTagKey::Date | StandardTagKey::ReleaseDate | StandardTagKey::OriginalDate
+                if tags.year.is_none() =>
+            {
+                tags.year = leading_number(&tag.value).and_then(|year| i32::try_from(year).ok())
+            }
+            _ => {}
+        }
+    }
+}
+
+fn set_text(field: &mut Option<String>, value: &Value) {
+    if field.is_some() {
+        return;
+    }
+    let text = value.to_string();
+    let text = text.trim();
+    if !text.is_empty() {
+        *field = Some(text.to_string());
+    }
+}
+
+/// Parses values like `3`, `"03/12"` or `"2004-05-01"` into their leading number.
+fn leading_number(value: &Value) -> Option<u32> {
+    match value {
+        Value::UnsignedInt(n) => u32::try_from(*n).ok(),
+        Value::SignedInt(n) => u32::try_from(*n).ok(),
+        Value::String(s) => {
+            let digits: String = s
+                .trim()
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            digits.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+fn merge_missing(tags: &mut Tags, guess: Tags) {
+    tags.title = tags.title.take().or(guess.title);
+    tags.artist = tags.artist.take().or(guess.artist);
+    tags.track_number = tags.track_number.or(guess.track_number);
+}
+
+/// Guesses tags from names like "Artist - Title", "01 - Title" or "01. Artist - Title".
+pub fn parse_file_name(stem: &str) -> Tags {
+    let mut tags = Tags::default();
+    let mut rest = stem.trim();
+
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && digits < rest.len() {
+        let after = rest[digits..].trim_start_matches([' ', '.', '-', '_']);
+        if after.len() < rest.len() - digits {
+            tags.track_number = rest[..digits].parse().ok();
+            rest = after;
+        }
+    }
+
+    match rest.split_once(" - ") {
+        Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => {
+            tags.artist = Some(artist.trim().to_string());
+            tags.title = Some(title.trim().to_string());
+        }
+        _ if !rest.is_empty() => tags.title = Some(rest.to_string()),
+        _ => {}
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_artist_title() {
+        let tags = parse_file_name("Океан Ельзи - Обійми");
+        assert_eq!(tags.artist.as_deref(), Some("Океан Ельзи"));
+        assert_eq!(tags.title.as_deref(), Some("Обійми"));
+        assert_eq!(tags.track_number, None);
+    }
+
+    #[test]
+    fn test_parse_track_number() {
+        let tags = parse_file_name("07 - Intro");
+        assert_eq!(tags.track_number, Some(7));
+        assert_eq!(tags.artist, None);
+        assert_eq!(tags.title.as_deref(), Some("Intro"));
+
+        let tags = parse_file_name("01. Artist - Song - Live");
+        assert_eq!(tags.track_number, Some(1));
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!(tags.title.as_deref(), Some("Song - Live"));
+    }
+
+    #[test]
+    fn test_parse_plain_name() {
+        assert_eq!(parse_file_name("1979").title.as_deref(), Some("1979"));
+        assert_eq!(parse_file_name("untitled").title.as_deref(), Some("untitled"));
+    }
+
+    #[test]
+    fn test_leading_number() {
+        assert_eq!(leading_number(&Value::String("03/12".into())), Some(3));
+        assert_eq!(leading_number(&Value::String("2004-05-01".into())), Some(2004));
+        assert_eq!(leading_number(&Value::UnsignedInt(9)), Some(9));
+        assert_eq!(leading_number(&Value::String("n/a".into())), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Stable identifier of a track, derived from its absolute path.
+///
+/// The hash is computed with FNV-1a rather than `DefaultHasher`, whose output may change between
+/// Rust releases, so ids stay valid in anything persisted to disk.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct TrackId(pub u64);
+
+impl TrackId {
+    pub fn from_path(path: &Path) -> Self {
+        Self(fnv1a(path.as_os_str().as_encoded_bytes()))
+    }
+}
+
+impl fmt::Display for TrackId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Identity of a track's file content, so it is recognised after a rename or a move.
+///
+/// Hashing whole files would make scans slow, so only the size and the first and last
+/// [`ContentHash::SAMPLE`] bytes count. That is plenty to tell songs apart.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct ContentHash(pub u64);
+
+impl ContentHash {
+    pub const SAMPLE: u64 = 64 * 1024;
+
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut bytes = size.to_le_bytes().to_vec();
+        file.by_ref().take(Self::SAMPLE).read_to_end(&mut bytes)?;
+        let tail = size.saturating_sub(Self::SAMPLE).min(Self::SAMPLE);
+        if tail > 0 {
+            file.seek(SeekFrom::End(-(tail as i64)))?;
+            file.take(tail).read_to_end(&mut bytes)?;
+        }
+        Ok(Self(fnv1a(&bytes)))
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes
+        .iter()
+        .fold(OFFSET, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(PRIME))
+}
+
+/// Descriptive tags of a track, read from the file or guessed from its name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+}
+
+/// A single audio file of the library.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Track {
+    pub id: TrackId,
+    /// Absolute path to the file.
+    pub path: PathBuf,
+    /// Survives renames, unlike `id`; what favorites and playlists remember tracks by.
+    #[serde(default)]
+    pub content_hash: ContentHash,
+    pub tags: Tags,
+    pub duration: Duration,
+    /// Codec name, e.g. "MP3" or "FLAC".
+    pub format: String,
+    /// Average bitrate in kbit/s.
+    pub bitrate: Option<u32>,
+    pub sample_rate: Option<u32>,
+    /// When the file appeared, in seconds since the Unix epoch: its creation time where the
+    /// filesystem records one, its modification time otherwise. Zero if unknown.
+    #[serde(default)]
+    pub added: u64,
+}
+
+impl Track {
+    /// Creates a track with no tags or stream properties yet.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            id: TrackId::from_path(&path),
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// Title from the tags, or the file name when the file has none.
+    pub fn title(&self) -> String {
+        self.tags.title.clone().unwrap_or_else(|| {
+            self.path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Невідомий трек".to_string())
+        })
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.tags.artist.as_deref()
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.tags.album.as_deref()
+    }
+
+    /// "Artist — Title", or just the title when the artist is unknown.
+    pub fn display_name(&self) -> String {
+        match self.artist() {
+            Some(artist) => format!("{artist} — {}", self.title()),
+            None => self.title(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_track_id_is_stable() {
+        // FNV-1a test vector; a changed value would orphan every id saved on disk.
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        let path = Path::new("/music/song.mp3");
+        assert_eq!(TrackId::from_path(path), TrackId::from_path(path));
+        assert_ne!(
+            TrackId::from_path(path),
+            TrackId::from_path(Path::new("/music/song2.mp3"))
+        );
+    }
+
+    #[test]
+    fn test_content_hash_follows_the_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            dir.path().join("a.mp3"),
+            dir.path().join("b.mp3"),
+            dir.path().join("c.mp3"),
+        );
+        let mut bytes: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        fs::write(&a, &bytes).unwrap();
+        fs::write(&b, &bytes).unwrap();
+        // Same length, one byte different near the end
+        bytes[199_000] ^= 1;
+        fs::write(&c, &bytes).unwrap();
+
+        let hash = ContentHash::of(&a).unwrap();
+        assert_eq!(ContentHash::of(&b).unwrap(), hash);
+        assert_ne!(ContentHash::of(&c).unwrap(), hash);
+        fs::write(&c, b"short").unwrap();
+        assert_ne!(ContentHash::of(&c).unwrap(), hash);
+    }
+}
@@ -0,0 +1,183 @@
+//! Keeps the library in sync with the music folders while the app is running.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::{Instant, timeout_at},
+};
+use tracing::{debug, warn};
+
+use super::{
+    cache::LibraryCache,
+    scanner::{self, ScanReport},
+};
+use crate::action::Action;
+
+/// How long the folders must stay quiet before a batch of changes is applied.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Upper bound on how long changes are held back while files keep changing, e.g. during a
+/// large copy.
+const MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Watches the library roots and reports added, changed and removed files as
+/// [`Action::LibraryTrackAdded`] and [`Action::LibraryTrackRemoved`].
+///
+/// Changes are collected until the scan that runs at startup hands over its cache, so nothing
+/// that happens during the scan is lost. Watching stops when the value is dropped.
+pub struct LibraryWatcher {
+    _watcher: RecommendedWatcher,
+    task: JoinHandle<()>,
+}
+
+impl LibraryWatcher {
+    pub fn start(
+        roots: &[PathBuf],
+        cache: oneshot::Receiver<LibraryCache>,
+        cache_path: PathBuf,
+        tx: mpsc::UnboundedSender<Action>,
+    ) -> notify::Result<Self> {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !event.kind.is_access() => {
+                    let _ = event_tx.send(event.paths);
+                }
+                Ok(_) => {}
+                Err(err) => warn!("File watcher error: {err}"),
+            })?;
+        for root in roots {
+            // Tracks are stored under canonical paths, so events must use them as well.
+            let root = fs::canonicalize(root).unwrap_or_else(|_| root.clone());
+            if let Err(err) = watcher.watch(&root, RecursiveMode::Recursive) {
+                warn!("Cannot watch {}: {err}", root.display());
+            }
+        }
+        let task = tokio::spawn(async move {
+            let Ok(cache) = cache.await else {
+                return;
+            };
+            watch_loop(event_rx, cache, cache_path, tx).await;
+        });
+        Ok(Self {
+            _watcher: watcher,
+            task,
+        })
+    }
+}
+
+impl Drop for LibraryWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn watch_loop(
+    mut events: mpsc::UnboundedReceiver<Vec<PathBuf>>,
+    mut cache: LibraryCache,
+    cache_path: PathBuf,
+    tx: mpsc::UnboundedSender<Action>,
+) {
+    while let Some(paths) = events.recv().await {
+        let mut changed: BTreeSet<PathBuf> = paths.into_iter().collect();
+        let deadline = Instant::now() + MAX_DELAY;
+        loop {
+            let quiet = (Instant::now() + DEBOUNCE).min(deadline);
+            match timeout_at(quiet, events.recv()).await {
+                Ok(Some(paths)) => changed.extend(paths),
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        debug!("Applying {} library changes", changed.len());
+
+        let tx = tx.clone();
+        let cache_path = cache_path.clone();
+        let applied = tokio::task::spawn_blocking(move || {
+            let actions = apply_changes(&changed, &mut cache);
+            if let Err(err) = cache.save(&cache_path) {
+                warn!("Failed to save library cache: {err}");
+            }
+            for action in actions {
+                let _ = tx.send(action);
+            }
+            cache
+        })
+        .await;
+        match applied {
+            Ok(updated) => cache = updated,
+            Err(err) => return warn!("Library watcher stopped: {err}"),
+        }
+    }
+}
+
+/// Updates `cache` for every changed path and returns the actions that bring the UI up to date.
+fn apply_changes(changed: &BTreeSet<PathBuf>, cache: &mut LibraryCache) -> Vec<Action> {
+    let mut actions = Vec::new();
+    for path in changed {
+        match fs::metadata(path) {
+            Ok(meta) if meta.is_dir() => {
+                let mut report = ScanReport::default();
+                for file in scanner::walk(std::slice::from_ref(path), &mut report) {
+                    add_file(&file, cache, &mut actions);
+                }
+            }
+            Ok(meta) if meta.is_file() && !scanner::is_hidden(path) => {
+                add_file(path, cache, &mut actions)
+            }
+            Ok(_) => {}
+            Err(_) => {
+                // Gone: a removed file, a removed folder or the old name of a renamed one.
+                cache.remove_under(path);
+                actions.push(Action::LibraryTrackRemoved(path.clone()));
+            }
+        }
+    }
+    actions
+}
+
+fn add_file(path: &Path, cache: &mut LibraryCache, actions: &mut Vec<Action>) {
+    match scanner::read_track(path, cache) {
+        Ok((stamp, Some(track))) => {
+            cache.insert(stamp, track.clone());
+            actions.push(Action::LibraryTrackAdded(Box::new(track)));
+        }
+        Ok((stamp, None)) => cache.ignore(path.to_path_buf(), stamp),
+        // Most likely a file that is still being copied; the next event will pick it up.
+        Err(err) => debug!("Skipping {}: {err}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_wav;
+
+    #[test]
+    fn test_apply_added_and_removed_files() {
+        let root = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(root.path()).unwrap();
+        let album = root.join("Album");
+        fs::create_dir(&album).unwrap();
+        write_wav(&album.join("a.wav"), [0]);
+        let mut cache = LibraryCache::default();
+
+        // A folder moved into the library brings its files along.
+        let actions = apply_changes(&BTreeSet::from([album.clone()]), &mut cache);
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::LibraryTrackAdded(track)] if track.path == album.join("a.wav")
+        ));
+
+        fs::remove_dir_all(&album).unwrap();
+        let actions = apply_changes(&BTreeSet::from([album.clone()]), &mut cache);
+        assert_eq!(actions, vec![Action::LibraryTrackRemoved(album)]);
+    }
+}
@@ -6,11 +6,19 @@ use crate::app::App;
 
 mod action;
 mod app;
+mod audio;
 mod cli;
 mod components;
 mod config;
 mod errors;
+mod history;
+mod library;
 mod logging;
+mod queue;
+mod session;
+mod storage;
+#[cfg(test)]
+mod test_util;
 mod tui;
 
 #[tokio::main]
@@ -19,7 +27,7 @@ async fn main() -> Result<()> {
     crate::logging::init()?;
 
     let args = Cli::parse();
-    let mut app = App::new(args.tick_rate, args.frame_rate)?;
+    let mut app = App::new(&args)?;
     app.run().await?;
     Ok(())
 }
@@ -0,0 +1,505 @@
+//! The play queue: what plays next, kept apart from the cursor in the song list.
+
+use std::path::Path;
+
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use crate::library::Track;
+
+/// What happens when the play order runs out or a track ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Repeat {
+    /// Stop after the last track.
+    #[default]
+    Off,
+    /// Start over from the first track.
+    All,
+    /// Play the current track again when it ends.
+    One,
+}
+
+impl Repeat {
+    /// The mode after this one: off → all → one → off.
+    pub fn cycle(self) -> Self {
+        match self {
+            Repeat::Off => Repeat::All,
+            Repeat::All => Repeat::One,
+            Repeat::One => Repeat::Off,
+        }
+    }
+}
+
+/// Tracks lined up for playback and the one playing now.
+#[derive(Debug, Clone, Default)]
+pub struct Queue {
+    tracks: Vec<Track>,
+    /// Index of the track playing now, `None` before the first one starts.
+    current: Option<usize>,
+    /// Set while there is no current track because it was removed: the position in play order
+    /// where playback goes on.
+    resume_at: Option<usize>,
+    repeat: Repeat,
+    /// Play order while shuffle is on: a permutation of indices into `tracks`. Everything before
+    /// the current track is the history that [`Queue::previous`] walks back through.
+    order: Option<Vec<usize>>,
+    rng: Rng,
+}
+
+impl Queue {
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&Track> {
+        self.tracks.get(self.current?)
+    }
+
+    pub fn set_repeat(&mut self, repeat: Repeat) {
+        self.repeat = repeat;
+    }
+
+    /// Turns shuffle on or off. A new play order starts from the current track; turning it off
+    /// continues in list order from wherever playback is.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        match (shuffle, self.order.is_some()) {
+            (true, false) => {
+                self.order = Some(self.shuffled_order());
+                self.resume_at = self.resume_at.map(|_| 0);
+            }
+            (false, true) => {
+                // Далі грає той самий трек, тепер за порядком списку
+                let order = self.order.take().unwrap_or_default();
+                let len = self.tracks.len();
+                self.resume_at = self
+                    .resume_at
+                    .map(|position| order.get(position).copied().unwrap_or(len));
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces the queue with `tracks` and makes `start` the current one.
+    pub fn replace(&mut self, tracks: Vec<Track>, start: usize) -> Option<&Track> {
+        self.restore(tracks, Some(start));
+        self.current()
+    }
+
+    /// Replaces the queue with `tracks`, e.g. from a saved session, `current` pointing into them.
+    pub fn restore(&mut self, tracks: Vec<Track>, current: Option<usize>) {
+        self.tracks = tracks;
+        self.current = current.filter(|&current| current < self.tracks.len());
+        self.resume_at = None;
+        if self.order.is_some() {
+            self.order = Some(self.shuffled_order());
+        }
+    }
+
+    /// Adds a track at the end. With shuffle on it gets a random place among the tracks that
+    /// have not played yet.
+    pub fn enqueue(&mut self, track: Track) {
+        let index = self.tracks.len();
+        self.tracks.push(track);
+        let upcoming = self.upcoming_position();
+        if let Some(order) = &mut self.order {
+            let at = self.rng.usize(upcoming..=order.len());
+            order.insert(at, index);
+        }
+    }
+
+    /// Inserts a track right after the current one.
+    pub fn play_next(&mut self, track: Track) {
+        let upcoming = self.upcoming_position();
+        let at = match (self.current, self.resume_at) {
+            (Some(current), _) => current + 1,
+            // Туди, де був вилучений трек, перед тим, що грав би далі
+            (None, Some(position)) if position < self.tracks.len() => self.at(position),
+            (None, Some(_)) => self.tracks.len(),
+            (None, None) => 0,
+        };
+        self.tracks.insert(at, track);
+        if let Some(order) = &mut self.order {
+            for index in order.iter_mut().filter(|index| **index >= at) {
+                *index += 1;
+            }
+            order.insert(upcoming, at);
+        }
+    }
+
+    /// Makes the track at `index` the current one. With shuffle on it is moved right after the
+    /// track it interrupts, so "previous" leads back there.
+    pub fn jump(&mut self, index: usize) -> Option<&Track> {
+        if index >= self.tracks.len() {
+            return None;
+        }
+        if self.current != Some(index) {
+            let upcoming = self.upcoming_position();
+            if let Some(order) = &mut self.order {
+                let from = order.iter().position(|&i| i == index)?;
+                order.remove(from);
+                order.insert(
+                    if from < upcoming {
+                        upcoming - 1
+                    } else {
+                        upcoming
+                    },
+                    index,
+                );
+            }
+        }
+        self.current = Some(index);
+        self.resume_at = None;
+        self.current()
+    }
+
+    /// Moves to the next track in play order. At the end it wraps around only with
+    /// [`Repeat::All`], otherwise there is nothing next.
+    pub fn advance(&mut self) -> Option<&Track> {
+        let next = self.next_position()?;
+        self.current = Some(self.at(next));
+        self.resume_at = None;
+        self.current()
+    }
+
+    /// Moves to the previous track in play order, wrapping around at the start only with
+    /// [`Repeat::All`].
+    pub fn previous(&mut self) -> Option<&Track> {
+        let len = self.tracks.len();
+        let previous = match self.position().or(self.resume_at) {
+            _ if len == 0 => return None,
+            None => 0,
+            Some(position) if position > 0 => position.min(len) - 1,
+            Some(_) if self.repeat == Repeat::All => len - 1,
+            Some(_) => return None,
+        };
+        self.current = Some(self.at(previous));
+        self.resume_at = None;
+        self.current()
+    }
+
+    /// The track to play after the current one ended on its own. A removed current track can
+    /// not be repeated, so playback moves on from where it was.
+    pub fn next_after_finished(&mut self) -> Option<&Track> {
+        match self.repeat {
+            Repeat::One if self.current.is_some() => self.current(),
+            _ => self.advance(),
+        }
+    }
+
+    /// What [`Queue::next_after_finished`] would return, without moving there.
+    pub fn upcoming(&self) -> Option<&Track> {
+        match self.repeat {
+            Repeat::One if self.current.is_some() => self.current(),
+            _ => self.tracks.get(self.at(self.next_position()?)),
+        }
+    }
+
+    /// Removes the track at `index`. Removing the current track keeps playback going: the queue
+    /// is left without a current track, and the track after it becomes the next one.
+    pub fn remove(&mut self, index: usize) -> Option<Track> {
+        if index >= self.tracks.len() {
+            return None;
+        }
+        let position = self.position_of(index);
+        let current = match self.current {
+            Some(current) if current == index => {
+                self.resume_at = Some(position);
+                None
+            }
+            current => current,
+        };
+        if let Some(resume_at) = &mut self.resume_at
+            && position < *resume_at
+        {
+            *resume_at -= 1;
+        }
+        let track = self.tracks.remove(index);
+        if let Some(order) = &mut self.order {
+            order.remove(position);
+            for i in order.iter_mut().filter(|i| **i > index) {
+                *i -= 1;
+            }
+        }
+        self.current = current.map(|current| {
+            if current > index {
+                current - 1
+            } else {
+                current
+            }
+        });
+        Some(track)
+    }
+
+    /// Drops every track at `path` or inside it, e.g. after it was deleted from disk.
+    pub fn remove_under(&mut self, path: &Path) {
+        let mut index = 0;
+        while index < self.tracks.len() {
+            if self.tracks[index].path.starts_with(path) {
+                self.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Swaps the track at `index` with its neighbour above (`up`) or below. Returns the new index
+    /// of the moved track.
+    pub fn move_track(&mut self, index: usize, up: bool) -> Option<usize> {
+        let other = if up { index.checked_sub(1)? } else { index + 1 };
+        if index >= self.tracks.len() || other >= self.tracks.len() {
+            return None;
+        }
+        self.tracks.swap(index, other);
+        let swap = |i: usize| match i {
+            i if i == index => other,
+            i if i == other => index,
+            i => i,
+        };
+        if let Some(order) = &mut self.order {
+            order.iter_mut().for_each(|i| *i = swap(*i));
+        }
+        self.current = self.current.map(swap);
+        Some(other)
+    }
+
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.current = None;
+        self.resume_at = None;
+        if let Some(order) = &mut self.order {
+            order.clear();
+        }
+    }
+
+    /// Fisher–Yates shuffle of every track, with the current one (if any) kept first.
+    fn shuffled_order(&mut self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.tracks.len()).collect();
+        if let Some(current) = self.current {
+            order.swap(0, current);
+        }
+        let first = usize::from(self.current.is_some());
+        for i in (first + 1..order.len()).rev() {
+            let j = self.rng.usize(first..=i);
+            order.swap(i, j);
+        }
+        order
+    }
+
+    fn next_position(&self) -> Option<usize> {
+        let len = self.tracks.len();
+        match self.position() {
+            _ if len == 0 => None,
+            None => match self.resume_at {
+                Some(position) if position < len => Some(position),
+                Some(_) if self.repeat != Repeat::All => None,
+                _ => Some(0),
+            },
+            Some(position) if position + 1 < len => Some(position + 1),
+            Some(_) if self.repeat == Repeat::All => Some(0),
+            Some(_) => None,
+        }
+    }
+
+    /// Where in play order the track after the current one goes, ignoring repeat.
+    fn upcoming_position(&self) -> usize {
+        match self.position() {
+            Some(position) => position + 1,
+            None => self.resume_at.unwrap_or_default(),
+        }
+    }
+
+    /// Where the current track is in play order.
+    fn position(&self) -> Option<usize> {
+        self.current.map(|current| self.position_of(current))
+    }
+
+    fn position_of(&self, index: usize) -> usize {
+        match &self.order {
+            Some(order) => order.iter().position(|&i| i == index).unwrap_or(index),
+            None => index,
+        }
+    }
+
+    /// The track index at `position` in play order.
+    fn at(&self, position: usize) -> usize {
+        match &self.order {
+            Some(order) => order[position],
+            None => position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn track(name: &str) -> Track {
+        Track::new(PathBuf::from(format!("/music/{name}.mp3")))
+    }
+
+    fn titles(queue: &Queue) -> Vec<String> {
+        queue.tracks().iter().map(|t| t.title()).collect()
+    }
+
+    fn queue_of(names: &[&str]) -> Queue {
+        let mut queue = Queue::default();
+        queue.replace(names.iter().map(|name| track(name)).collect(), 0);
+        queue
+    }
+
+    #[test]
+    fn test_play_next_goes_after_current() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.advance();
+        queue.play_next(track("x"));
+        queue.enqueue(track("z"));
+        assert_eq!(titles(&queue), vec!["a", "b", "x", "c", "z"]);
+        assert_eq!(queue.advance().unwrap().title(), "x");
+    }
+
+    #[test]
+    fn test_advance_and_previous_wrap_with_repeat_all() {
+        let mut queue = queue_of(&["a", "b"]);
+        queue.set_repeat(Repeat::All);
+        assert_eq!(queue.advance().unwrap().title(), "b");
+        assert_eq!(queue.advance().unwrap().title(), "a");
+        assert_eq!(queue.previous().unwrap().title(), "b");
+        assert_eq!(Queue::default().advance(), None);
+    }
+
+    #[test]
+    fn test_repeat_modes() {
+        let mut queue = queue_of(&["a", "b"]);
+        queue.advance();
+        assert_eq!(queue.advance(), None);
+        assert_eq!(queue.previous().unwrap().title(), "a");
+        assert_eq!(queue.previous(), None);
+        assert_eq!(queue.upcoming().unwrap().title(), "b");
+        queue.set_repeat(Repeat::One);
+        assert_eq!(queue.upcoming().unwrap().title(), "a");
+        assert_eq!(queue.next_after_finished().unwrap().title(), "a");
+        // Skipping by hand still moves on
+        assert_eq!(queue.advance().unwrap().title(), "b");
+        assert_eq!(Repeat::Off.cycle().cycle().cycle(), Repeat::Off);
+    }
+
+    #[test]
+    fn test_shuffle_plays_every_track_once_and_previous_retraces() {
+        let names: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let mut queue = queue_of(&names.iter().map(String::as_str).collect::<Vec<_>>());
+        queue.rng = Rng::with_seed(7);
+        queue.set_shuffle(true);
+
+        let mut played = vec![queue.current().unwrap().title()];
+        while let Some(upcoming) = queue.upcoming().map(Track::title) {
+            assert_eq!(queue.advance().unwrap().title(), upcoming);
+            played.push(upcoming);
+        }
+        assert_eq!(queue.advance(), None);
+        assert_ne!(played, names);
+        let mut sorted = played.clone();
+        sorted.sort_by_key(|name| name.parse::<usize>().unwrap());
+        assert_eq!(sorted, names);
+
+        for expected in played.iter().rev().skip(1) {
+            assert_eq!(&queue.previous().unwrap().title(), expected);
+        }
+        assert_eq!(queue.previous(), None);
+    }
+
+    #[test]
+    fn test_shuffle_play_next_and_remove() {
+        let mut queue = queue_of(&["a", "b", "c", "d"]);
+        queue.set_shuffle(true);
+        queue.play_next(track("x"));
+        assert_eq!(queue.advance().unwrap().title(), "x");
+        let upcoming = queue.advance().unwrap().title();
+        queue.previous();
+        // Removing the current track makes the one after it in play order next
+        queue.remove(queue.current_index().unwrap());
+        assert_eq!(queue.advance().unwrap().title(), upcoming);
+    }
+
+    #[test]
+    fn test_remove_keeps_the_current_track() {
+        let mut queue = queue_of(&["a", "b", "c", "d"]);
+        queue.jump(2);
+        queue.remove(0);
+        queue.remove(2);
+        assert_eq!(titles(&queue), vec!["b", "c"]);
+        assert_eq!(queue.current().unwrap().title(), "c");
+    }
+
+    #[test]
+    fn test_removing_current_plays_the_next_one() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.jump(1);
+        queue.remove(1);
+        assert_eq!(queue.advance().unwrap().title(), "c");
+    }
+
+    #[test]
+    fn test_removing_current_under_repeat_one_moves_on() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.set_repeat(Repeat::One);
+        queue.jump(1);
+        queue.remove(1);
+        assert_eq!(queue.current(), None);
+        assert_eq!(queue.upcoming().unwrap().title(), "c");
+        assert_eq!(queue.next_after_finished().unwrap().title(), "c");
+        assert_eq!(queue.next_after_finished().unwrap().title(), "c");
+    }
+
+    #[test]
+    fn test_play_next_and_previous_after_removing_current() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        queue.jump(1);
+        queue.remove(1);
+        queue.play_next(track("x"));
+        assert_eq!(titles(&queue), vec!["a", "x", "c"]);
+        assert_eq!(queue.upcoming().unwrap().title(), "x");
+        assert_eq!(queue.previous().unwrap().title(), "a");
+
+        queue.set_shuffle(true);
+        queue.remove(0);
+        queue.play_next(track("y"));
+        assert_eq!(queue.advance().unwrap().title(), "y");
+    }
+
+    #[test]
+    fn test_move_track_follows_current() {
+        let mut queue = queue_of(&["a", "b", "c"]);
+        assert_eq!(queue.move_track(0, false), Some(1));
+        assert_eq!(titles(&queue), vec!["b", "a", "c"]);
+        assert_eq!(queue.current().unwrap().title(), "a");
+        assert_eq!(queue.move_track(0, true), None);
+        assert_eq!(queue.move_track(2, false), None);
+    }
+
+    #[test]
+    fn test_remove_under_folder() {
+        let mut queue = queue_of(&["a", "b"]);
+        queue.enqueue(Track::new(PathBuf::from("/other/c.mp3")));
+        queue.remove_under(Path::new("/music"));
+        assert_eq!(titles(&queue), vec!["c"]);
+        assert_eq!(queue.current(), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! What was playing when the app last closed, so the next launch can pick up from there.
+
+use std::{path::Path, time::Duration};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{library::Track, storage};
+
+/// File in the data directory holding the last [`Session`].
+pub const FILE_NAME: &str = "session.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// The play queue, in order.
+    pub tracks: Vec<Track>,
+    /// Index into `tracks` of the track that was playing.
+    pub current: Option<usize>,
+    /// How far into the current track playback got.
+    pub position: Duration,
+}
+
+impl Session {
+    /// Reads the saved session, leaving out tracks whose files are gone since.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let Some(mut session) = storage::load_json::<Self>(path)? else {
+            return Ok(None);
+        };
+        let current = session
+            .current
+            .and_then(|index| session.tracks.get(index).cloned());
+        session.tracks.retain(|track| track.path.exists());
+        session.current = current.and_then(|current| {
+            session
+                .tracks
+                .iter()
+                .position(|track| track.path == current.path)
+        });
+        if session.current.is_none() {
+            session.position = Duration::ZERO;
+        }
+        Ok(Some(session))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        storage::save_json(path, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_load_skips_deleted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracks: Vec<Track> = ["a.mp3", "b.mp3", "c.mp3"]
+            .iter()
+            .map(|name| Track::new(dir.path().join(name)))
+            .collect();
+        for track in &tracks {
+            fs::write(&track.path, b"").unwrap();
+        }
+        let path = dir.path().join(FILE_NAME);
+        let session = Session {
+            tracks: tracks.clone(),
+            current: Some(2),
+            position: Duration::from_millis(61_500),
+        };
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), Some(session.clone()));
+
+        fs::remove_file(&tracks[0].path).unwrap();
+        let restored = Session::load(&path).unwrap().unwrap();
+        assert_eq!(restored.tracks, tracks[1..]);
+        assert_eq!(restored.current, Some(1));
+        assert_eq!(restored.position, session.position);
+
+        fs::remove_file(&tracks[2].path).unwrap();
+        let restored = Session::load(&path).unwrap().unwrap();
+        assert_eq!(restored.current, None);
+        assert_eq!(restored.position, Duration::ZERO);
+        assert_eq!(Session::load(&dir.path().join("none.json")).unwrap(), None);
+    }
+}
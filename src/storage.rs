@@ -0,0 +1,69 @@
+//! Small JSON files kept in the data directory (library cache, player state, playlists, ...).
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use color_eyre::Result;
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::warn;
+
+/// Reads a JSON file, returning `None` if it does not exist yet.
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(Some(serde_json::from_slice(&contents)?))
+}
+
+/// Writes a JSON file atomically: the data goes to a temporary file first, which then replaces
+/// the old one, so a crash mid-write never leaves a truncated file behind.
+pub fn save_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(value)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Reads a JSON Lines log, one value per line. Lines that do not parse, like one cut short by a
+/// crash, are skipped.
+pub fn load_json_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let values = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|err| warn!("Skipping a bad line in {}: {err}", path.display()))
+                .ok()
+        })
+        .collect();
+    Ok(values)
+}
+
+/// Appends one value to a JSON Lines log, creating the file if needed.
+pub fn append_json_line<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    // Один write на рядок, щоб рядки з різних записів не перемішались
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)?;
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! Helpers shared by the test modules.
+
+use std::path::Path;
+
+/// Writes `samples` as a mono 8 kHz, 16-bit WAV file: cheap to decode and easy to count in.
+pub fn write_wav(path: &Path, samples: impl IntoIterator<Item = i16>) {
+    write_wav_as(path, 1, 8000, samples);
+}
+
+/// Like [`write_wav`] in another format; `samples` are interleaved.
+pub fn write_wav_as(
+    path: &Path,
+    channels: u16,
+    sample_rate: u32,
+    samples: impl IntoIterator<Item = i16>,
+) {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+}